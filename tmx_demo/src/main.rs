@@ -5,14 +5,14 @@ extern crate hex;
 extern crate futures;
 
 use std::collections::HashMap;
-use tmx_reader::Map;
+use tmx_reader::{AnimationClock, Map, Rect, World, WorldMap};
 use regex::Regex;
 use std::str;
 
 use quicksilver::{
     Result,
     geom::{Shape, Vector, Rectangle, Transform},
-    graphics::{Background::Img, Color, Image, View},
+    graphics::{Background::{Col, Img}, Color, Image, View},
     input::{Key},
     lifecycle::{Settings, State, Window, run}
 };
@@ -24,49 +24,120 @@ mod generated_mod {
 const WIDTH: i32 = 800;
 const HEIGHT: i32 = 600;
 
-const FLIP_HORIZONTALLY:[[f32; 3]; 3] = [[1f32, 0f32, 1f32], [0f32, -1f32, 1f32], [0f32, 0f32, 1f32]];
-
-const FLIP_VERTICALLY:[[f32; 3]; 3] = [[-1f32, 0f32, 1f32], [0f32, 1f32, 1f32], [0f32, 0f32, 1f32]];
-
-struct TmxDemo {
+/// A map of the world that is currently loaded, `x` and `y` place it in world space.
+struct Room {
     map: Map,
     tile_images: HashMap<i64, Image>,
+    /// Every map numbers its tiles with its own firstgids, so each room animates them with a clock of its own.
+    clock: AnimationClock,
+    x: f32,
+    y: f32,
+}
+
+struct TmxDemo {
+    resources: HashMap<&'static str, &'static[u8]>,
+    world: World,
+    rooms: HashMap<String, Room>,
+    /// Rooms whose map couldn't be read, they are drawn as a red area instead of being loaded again and again.
+    broken: HashMap<String, WorldMap>,
+    textures: HashMap<String, Image>,
+    /// Milliseconds since the start, rooms loaded later catch up with it.
+    elapsed: u64,
     view: Rectangle,
     color: Color,
 }
 
-impl State for TmxDemo {
-
-    fn new() -> Result<TmxDemo> {
+impl TmxDemo {
+    /// Loads the rooms within a screen of the view and drops the ones further away.
+    fn stream_rooms(&mut self) {
+        let area = Rect {
+            x: (self.view.pos.x - self.view.size.x) as i32,
+            y: (self.view.pos.y - self.view.size.y) as i32,
+            width: (self.view.size.x * 3.0) as i32,
+            height: (self.view.size.y * 3.0) as i32,
+        };
+        let nearby: Vec<WorldMap> = self.world.intersecting(area).cloned().collect();
+
+        self.rooms.retain(|filename, _| nearby.iter().any(|map| &map.filename == filename));
+        for world_map in nearby {
+            if !self.rooms.contains_key(&world_map.filename) && !self.broken.contains_key(&world_map.filename) {
+                // A broken room is marked, the rest of the world is still worth walking around in.
+                let map = match self.world.load_map(&world_map, &self.resources) {
+                    Ok(map) => map,
+                    Err(_) => {
+                        self.broken.insert(world_map.filename.clone(), world_map);
+                        continue;
+                    }
+                };
+                let tile_images = cut_tile_images(&map, &self.resources, &mut self.textures);
+                let mut clock = AnimationClock::new(&map);
+                clock.advance(self.elapsed);
+                self.rooms.insert(world_map.filename.clone(), Room { map, tile_images, clock, x: world_map.x as f32, y: world_map.y as f32 });
+            }
+        }
+    }
+}
 
-        let resources: HashMap<&'static str, &'static[u8]> = generated_mod::static_content();
+/// Every texture is loaded once, the tiles are cut from it.
+fn cut_tile_images(map: &Map, resources: &HashMap<&'static str, &'static[u8]>, textures: &mut HashMap<String, Image>) -> HashMap<i64, Image> {
+    let mut tile_images: HashMap<i64, Image> = HashMap::new();
+
+    map.tilesets.iter().for_each(|tileset| {
+        let ids: Vec<i64> = if tileset.image.is_some() {
+            (0..i64::from(tileset.tilecount)).collect()
+        } else {
+            tileset.tiles.iter().map(|tile| tile.id).collect()
+        };
+
+        ids.into_iter().for_each(|id| {
+            if let (Some(image), Some(rect)) = (tileset.tile_image(id), tileset.source_rect(id)) {
+                let texture = textures.entry(image.source.clone())
+                    .or_insert_with(|| Image::from_bytes(resources.get(&image.source[..]).unwrap()).unwrap());
+                let region = Rectangle::new((rect.x, rect.y), (rect.width, rect.height));
+                tile_images.insert(tileset.firstgid + id, texture.subimage(region));
+            }
+        });
+    });
 
+    tile_images
+}
 
-        let tmx_file = resources.get("sandbox.tmx").unwrap().to_vec();
-        let tmx_content = str::from_utf8(&tmx_file);
+impl State for TmxDemo {
 
-        let map = Map::new(tmx_content.unwrap());
+    fn new() -> Result<TmxDemo> {
 
-        let mut tile_images: HashMap<i64, Image> = HashMap::new();
+        let resources: HashMap<&'static str, &'static[u8]> = generated_mod::static_content();
 
-        let firstgid = map.tileset.firstgid;
+        let world_file = resources.get("sandbox.world").unwrap().to_vec();
+        let world = World::parse(str::from_utf8(&world_file).unwrap()).expect("Unable to read sandbox.world");
 
-        map.tileset.tiles.iter().for_each(|tile| {
-            let pos = firstgid + tile.id;
-            let source:String = tile.image.as_ref().unwrap().source.clone();
-            tile_images.insert(pos, Image::from_bytes(resources.get(&source[..]).unwrap()).unwrap());
-        });
+        let first = world.maps.first().expect("The world has no maps");
+        let map = world.load_map(first, &resources).expect("Unable to read the first map");
 
         let re = Regex::new(r"^#?([a-f\d]{2})([a-f\d]{2})([a-f\d]{2})$").unwrap();
 
-        let captures = re.captures(&map.backgroundcolor).unwrap();
-
-        let r = hex::decode(&captures.get(1).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
-        let g = hex::decode(&captures.get(2).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
-        let b = hex::decode(&captures.get(3).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
-
-        let color = Color { r: r, g: g, b: b, a: 1.0 };
-        Ok(TmxDemo { map, tile_images, view: Rectangle::new_sized((800, 600)), color })
+        // Maps without a background color of their own are drawn on black.
+        let color = match map.backgroundcolor.as_ref().and_then(|c| re.captures(c)) {
+            Some(captures) => {
+                let r = hex::decode(&captures.get(1).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
+                let g = hex::decode(&captures.get(2).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
+                let b = hex::decode(&captures.get(3).unwrap().as_str()).unwrap().get(0).unwrap().to_owned() as f32 / 255.0;
+                Color { r: r, g: g, b: b, a: 1.0 }
+            },
+            None => Color::BLACK,
+        };
+        let mut demo = TmxDemo {
+            resources,
+            world,
+            rooms: HashMap::new(),
+            broken: HashMap::new(),
+            textures: HashMap::new(),
+            elapsed: 0,
+            view: Rectangle::new_sized((800, 600)),
+            color,
+        };
+        demo.stream_rooms();
+        Ok(demo)
     }
 
     fn update(&mut self, window: &mut Window) -> Result<()> {
@@ -83,6 +154,10 @@ impl State for TmxDemo {
             self.view = self.view.translate((0, -40));
         }
         window.set_view(View::new(self.view));
+        self.stream_rooms();
+        let step = window.update_rate() as u64;
+        self.elapsed += step;
+        self.rooms.values_mut().for_each(|room| room.clock.advance(step));
         Ok(())
     }
 
@@ -91,39 +166,65 @@ impl State for TmxDemo {
 
         window.clear(self.color)?;
 
-        let map = &mut self.map;
-        let images = &mut self.tile_images;
+        // Rooms that couldn't be read show up as a red area where their map would be.
+        for map in self.broken.values() {
+            window.draw(&Rectangle::new((map.x, map.y), (map.width, map.height)), Col(Color::RED));
+        }
 
-        let mut layers = Vec::new();
-        map.objectgroups.iter().for_each(|objectgroup| {
-            let mut vec = Vec::new();
-            objectgroup.objects.iter().for_each(|object| {
-                &object.gid.map(|tile_id| {
-                    vec.push((tile_id, object))
+        for room in self.rooms.values_mut() {
+            let map = &room.map;
+            let images = &mut room.tile_images;
+            let clock = &room.clock;
+            let (room_x, room_y) = (room.x, room.y);
+
+            // Only the tiles inside the view are drawn, in the render order of the map.
+            for layer in map.tile_layers().filter(|l| l.values.visible) {
+                let (offset_x, offset_y) = (room_x + layer.values.offsetx as f32, room_y + layer.values.offsety as f32);
+                let viewport = Rect {
+                    x: (self.view.pos.x - offset_x) as i32,
+                    y: (self.view.pos.y - offset_y) as i32,
+                    width: self.view.size.x as i32,
+                    height: self.view.size.y as i32,
+                };
+
+                for (x, y, gid) in map.render_cells(layer.layer, Some(viewport)) {
+                    if let Some(img) = images.get(&clock.current_gid(gid.id())) {
+                        let (left, top) = map.tile_to_pixel(x, y);
+                        let size = img.area().size;
+                        let rect = Rectangle::new((offset_x + left, offset_y + top + map.tileheight as f32 - size.y), size);
+
+                        // Quicksilver transforms around the center of the rectangle, like the gid does.
+                        let trans = Transform::from_array(gid.transform().to_matrix());
+                        window.draw_ex(&rect, Img(img), trans, layer.z as i32);
+                    }
+                }
+            }
+
+            let mut layers = Vec::new();
+            map.object_layers().filter(|l| l.values.visible).for_each(|objectgroup| {
+                let mut vec = Vec::new();
+                objectgroup.layer.objects.iter().for_each(|object| {
+                    &object.gid.map(|tile_id| {
+                        vec.push((tile_id, object))
+                    });
                 });
+                layers.push((objectgroup.z as i32, vec));
             });
-            layers.push(vec);
-        });
 
-        let mut z = 0;
-        layers.iter().for_each(|elems| {
-            z = z + 1;
-            elems.iter().for_each(|tileid_and_object| {
-                images.get_mut(&tileid_and_object.0).map(|img| {
-                    let object = tileid_and_object.1;
-                    let rect: Rectangle = Rectangle::new((object.x, object.y - object.height), (object.width, object.height));
-                    let mut trans: Transform = if object.rotation != 0.0  { Transform::rotate(object.rotation as f32) } else { Transform::IDENTITY };
-
-                    if object.flipped_horizontally {
-                        trans = trans * Transform::from_array(FLIP_VERTICALLY);
-                    }
-                    if object.flipped_vertically {
-                        trans = trans * Transform::from_array(FLIP_HORIZONTALLY);
-                    }
-                    window.draw_ex(&rect, Img(img),  trans, z);
+            layers.iter().for_each(|(z, elems)| {
+                let z = *z;
+                elems.iter().for_each(|tileid_and_object| {
+                    let gid = tileid_and_object.0;
+                    images.get_mut(&clock.current_gid(gid.id())).map(|img| {
+                        let object = tileid_and_object.1;
+                        let rect: Rectangle = Rectangle::new((room_x + object.x, room_y + object.y - object.height), (object.width, object.height));
+                        let rotation: Transform = if object.rotation != 0.0  { Transform::rotate(object.rotation as f32) } else { Transform::IDENTITY };
+                        let trans = rotation * Transform::from_array(gid.transform().to_matrix());
+                        window.draw_ex(&rect, Img(img),  trans, z);
+                    });
                 });
             });
-        });
+        }
 
         Ok(())
     }
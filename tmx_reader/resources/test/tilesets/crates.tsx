@@ -0,0 +1,9 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="crates" tilewidth="16" tileheight="16" tilecount="2" columns="2">
+ <image source="crates.png" width="32" height="16"/>
+ <tile id="1">
+  <objectgroup draworder="index" id="2">
+   <object id="1" template="../templates/hitbox.tx" x="2" y="10"/>
+  </objectgroup>
+ </tile>
+</tileset>
@@ -0,0 +1,5 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.2" name="dungeon" tilewidth="16" tileheight="16" tilecount="64" columns="8">
+ <tileoffset x="0" y="4"/>
+ <image source="dungeon.png" width="128" height="128"/>
+</tileset>
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use crate::{Map, Tile};
+
+/// A single frame of a tile animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// The local id of the tile shown, within the tileset of the animated tile.
+    pub tileid: i64,
+    /// How long the frame is shown, in milliseconds.
+    pub duration: u32,
+}
+
+impl Tile {
+    /// Returns the frame shown `elapsed` milliseconds after the animation started, it loops forever.
+    /// `None` if the tile isn't animated.
+    pub fn frame_at(&self, elapsed: u64) -> Option<&Frame> {
+        frame_at(&self.animation, elapsed)
+    }
+}
+
+fn frame_at(frames: &[Frame], elapsed: u64) -> Option<&Frame> {
+    let total: u64 = frames.iter().map(|f| u64::from(f.duration)).sum();
+    if total == 0 {
+        return frames.first();
+    }
+
+    let mut time = elapsed % total;
+    frames.iter().find(|frame| {
+        if time < u64::from(frame.duration) {
+            true
+        } else {
+            time -= u64::from(frame.duration);
+            false
+        }
+    })
+}
+
+struct Animation {
+    firstgid: i64,
+    frames: Vec<Frame>,
+}
+
+/// Keeps track of the time and tells which frame every animated tile of a map currently shows.
+///
+/// The clock copies the animations out of the map, so it can live next to the map instead of borrowing it.
+pub struct AnimationClock {
+    elapsed: u64,
+    /// Animated tiles by their gid.
+    animations: HashMap<i64, Animation>,
+}
+
+impl AnimationClock {
+    pub fn new(map: &Map) -> AnimationClock {
+        let animations = map.tilesets.iter()
+            .flat_map(|tileset| tileset.tiles.iter()
+                .filter(|tile| !tile.animation.is_empty())
+                .map(move |tile| (tileset.firstgid + tile.id, Animation {
+                    firstgid: tileset.firstgid,
+                    frames: tile.animation.clone(),
+                })))
+            .collect();
+
+        AnimationClock { elapsed: 0, animations }
+    }
+
+    /// Moves the clock forward by `delta` milliseconds.
+    pub fn advance(&mut self, delta: u64) {
+        self.elapsed += delta;
+    }
+
+    /// Milliseconds since the animations started.
+    pub fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: u64) {
+        self.elapsed = elapsed;
+    }
+
+    /// Returns the local tile id every animated tile currently shows, keyed by the gid of the animated tile.
+    pub fn current_tile_ids(&self) -> HashMap<i64, i64> {
+        self.animations.iter()
+            .filter_map(|(gid, animation)| Some((*gid, frame_at(&animation.frames, self.elapsed)?.tileid)))
+            .collect()
+    }
+
+    /// Returns the gid to draw in place of `gid`, which is `gid` itself for tiles without an animation.
+    /// Flip flags have to be cleared before.
+    pub fn current_gid(&self, gid: i64) -> i64 {
+        self.animations.get(&gid)
+            .and_then(|animation| Some(animation.firstgid + frame_at(&animation.frames, self.elapsed)?.tileid))
+            .unwrap_or(gid)
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::slice;
+
+use crate::{tile_count, Gid, Rect, TmxError};
+
+/// A block of tile data of an infinite map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// The cell of the top left tile, negative coordinates are allowed.
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Global tile ids in row-major order, including the flip flags.
+    pub data: Vec<Gid>,
+}
+
+impl Chunk {
+    /// Returns the global tile id at the given cell of the layer, including the flip flags.
+    /// `None` if the cell isn't covered by this chunk.
+    pub fn raw_gid_at(&self, x: i32, y: i32) -> Option<Gid> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        self.data.get(((y - self.y) * self.width + x - self.x) as usize).copied()
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    /// The cells covered by the chunk, in tiles.
+    pub fn bounds(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: self.width, height: self.height }
+    }
+}
+
+/// The chunks of a tile layer in an infinite map.
+///
+/// Tiled writes chunks of a single size, aligned to a grid of that size,
+/// so the chunk covering a cell is found with a single lookup.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunks {
+    chunks: Vec<Chunk>,
+    chunkwidth: i32,
+    chunkheight: i32,
+    /// Index into `chunks` by the position on the chunk grid.
+    index: HashMap<(i32, i32), usize>,
+}
+
+impl Chunks {
+    pub(crate) fn new(chunks: Vec<Chunk>) -> Result<Chunks, TmxError> {
+        let (chunkwidth, chunkheight) = match chunks.first() {
+            Some(chunk) => (chunk.width, chunk.height),
+            None => return Ok(Chunks::default()),
+        };
+
+        let mut index = HashMap::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            if chunk.width != chunkwidth || chunk.height != chunkheight || chunkwidth <= 0 || chunkheight <= 0
+                || chunk.x.rem_euclid(chunkwidth) != 0 || chunk.y.rem_euclid(chunkheight) != 0 {
+                return Err(TmxError::InvalidTileData(format!("Chunk at {},{} doesn't fit the {}x{} chunk grid",
+                    chunk.x, chunk.y, chunkwidth, chunkheight)));
+            }
+            // The far edges have to fit as well, cells are looked up relative to them.
+            let count = tile_count(chunk.width, chunk.height)
+                .filter(|_| chunk.x.checked_add(chunk.width).is_some() && chunk.y.checked_add(chunk.height).is_some())
+                .ok_or_else(|| TmxError::InvalidTileData(format!("Chunk at {},{} has an invalid size of {}x{}",
+                    chunk.x, chunk.y, chunk.width, chunk.height)))?;
+            if chunk.data.len() != count {
+                return Err(TmxError::InvalidTileData(format!("Chunk at {},{} has {} tiles but should have {}",
+                    chunk.x, chunk.y, chunk.data.len(), count)));
+            }
+            if index.insert((chunk.x / chunkwidth, chunk.y / chunkheight), i).is_some() {
+                return Err(TmxError::InvalidTileData(format!("Chunk at {},{} appears twice", chunk.x, chunk.y)));
+            }
+        }
+
+        Ok(Chunks { chunks, chunkwidth, chunkheight, index })
+    }
+
+    /// Returns the chunk covering the given cell, if there is one.
+    pub fn chunk_at(&self, x: i32, y: i32) -> Option<&Chunk> {
+        if self.chunks.is_empty() {
+            return None;
+        }
+        let i = self.index.get(&(x.div_euclid(self.chunkwidth), y.div_euclid(self.chunkheight)))?;
+        Some(&self.chunks[*i])
+    }
+
+    /// Returns the global tile id at the given cell, including the flip flags.
+    /// `None` if no chunk covers the cell.
+    pub fn raw_gid_at(&self, x: i32, y: i32) -> Option<Gid> {
+        self.chunk_at(x, y)?.raw_gid_at(x, y)
+    }
+
+    /// The smallest rectangle covering every chunk, in tiles. `None` if there are no chunks.
+    pub fn bounds(&self) -> Option<Rect> {
+        let first = self.chunks.first()?.bounds();
+        let (mut left, mut top) = (first.x, first.y);
+        let (mut right, mut bottom) = (first.x + first.width, first.y + first.height);
+
+        for chunk in &self.chunks {
+            left = left.min(chunk.x);
+            top = top.min(chunk.y);
+            right = right.max(chunk.x + chunk.width);
+            bottom = bottom.max(chunk.y + chunk.height);
+        }
+
+        Some(Rect { x: left, y: top, width: right.saturating_sub(left), height: bottom.saturating_sub(top) })
+    }
+
+    /// Returns the chunks overlapping the given rectangle of cells.
+    pub fn intersecting(&self, area: Rect) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter().filter(move |chunk| chunk.bounds().overlaps(area))
+    }
+
+    /// The chunks in the order they appear in the file.
+    pub fn iter(&self) -> slice::Iter<'_, Chunk> {
+        self.chunks.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Chunks {
+    type Item = &'a Chunk;
+    type IntoIter = slice::Iter<'a, Chunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter()
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Affine, Gid, Layer, Map, Object, ObjectShape, Objectgroup, TileRef};
+
+/// A collision shape of a tile in world coordinates, in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldShape {
+    /// Rectangles end up as polygons as well, flips and rotations don't keep them axis aligned.
+    Polygon(Vec<(f32, f32)>),
+    Polyline(Vec<(f32, f32)>),
+    /// `rotation` is in degrees, clockwise.
+    Ellipse { center: (f32, f32), radiusx: f32, radiusy: f32, rotation: f32 },
+    Point((f32, f32)),
+}
+
+/// Where a placed tile comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum ColliderSource<'a> {
+    Cell { layer: &'a Layer, x: i32, y: i32 },
+    Object { objectgroup: &'a Objectgroup, object: &'a Object },
+}
+
+/// One collision shape of a placed tile.
+#[derive(Debug, Clone)]
+pub struct Collider<'a> {
+    /// The gid of the placed tile, without flip flags.
+    pub gid: i64,
+    pub source: ColliderSource<'a>,
+    /// The object in the collision group of the tile the shape was created from.
+    pub object: &'a Object,
+    pub shape: WorldShape,
+}
+
+impl Map {
+    /// Returns the collision shapes of every tile placed in a tile layer or as a tile object, in world coordinates.
+    ///
+    /// Flips, the rotation and size of tile objects, tile offsets and layer offsets are applied.
+    /// Cells are placed with the tile aligned to the bottom left of their bounding box, see `Map::tile_to_pixel`.
+    pub fn collision_shapes(&self) -> Vec<Collider<'_>> {
+        let mut colliders = Vec::new();
+
+        for layer in self.tile_layers() {
+            for (x, y, gid) in layer.layer.tiles() {
+                let tile_ref = match self.tile_for_gid(gid.id()) {
+                    Some(tile_ref) => tile_ref,
+                    None => continue,
+                };
+
+                let (left, top) = self.tile_to_pixel(x, y);
+                let placement = |_width: f32, height: f32| Affine::translate(
+                    left + layer.values.offsetx as f32,
+                    top + (self.tileheight + layer.values.offsety) as f32 - height,
+                );
+
+                push_colliders(&mut colliders, &tile_ref, gid, ColliderSource::Cell { layer: layer.layer, x, y }, placement);
+            }
+        }
+
+        for objectgroup in self.object_layers() {
+            for object in &objectgroup.layer.objects {
+                let (gid, tile_ref) = match object.gid.and_then(|gid| Some((gid, self.tile_for_gid(gid.id())?))) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+
+                // Tile objects are placed by their bottom left corner and rotate around it.
+                let placement = |width: f32, height: f32| Affine::translate(0.0, -height)
+                    .scale_by(width, height, object.width, object.height)
+                    .then(Affine::rotate(object.rotation))
+                    .then(Affine::translate(
+                        object.x + objectgroup.values.offsetx as f32,
+                        object.y + objectgroup.values.offsety as f32,
+                    ));
+
+                let source = ColliderSource::Object { objectgroup: objectgroup.layer, object };
+                push_colliders(&mut colliders, &tile_ref, gid, source, placement);
+            }
+        }
+
+        colliders
+    }
+}
+
+/// `placement` maps the tile, given the size it has after flipping, into the world.
+fn push_colliders<'a>(
+    colliders: &mut Vec<Collider<'a>>,
+    tile_ref: &TileRef<'a>,
+    gid: Gid,
+    source: ColliderSource<'a>,
+    placement: impl Fn(f32, f32) -> Affine,
+) {
+    let collision = match tile_ref.tile.and_then(|tile| tile.collision.as_ref()) {
+        Some(collision) => collision,
+        None => return,
+    };
+
+    let (width, height) = match tile_ref.tile.and_then(|tile| tile.image.as_ref()) {
+        Some(image) => (image.width as f32, image.height.unwrap_or(tile_ref.tileset.tileheight) as f32),
+        None => (tile_ref.tileset.tilewidth as f32, tile_ref.tileset.tileheight as f32),
+    };
+
+    let (flip, width, height) = gid.transform_within(width, height);
+    let (offsetx, offsety) = tile_ref.tileset.tileoffset.as_ref().map(|o| (o.x, o.y)).unwrap_or((0, 0));
+    let tile = flip
+        .then(placement(width, height))
+        .then(Affine::translate(offsetx as f32, offsety as f32));
+
+    for object in &collision.objects {
+        let transform = Affine::rotate(object.rotation)
+            .then(Affine::translate(object.x, object.y))
+            .then(tile);
+
+        let points = |points: &[(f32, f32)]| points.iter().map(|p| transform.apply(*p)).collect::<Vec<(f32, f32)>>();
+
+        let shape = match &object.shape {
+            ObjectShape::Rectangle => WorldShape::Polygon(points(&[
+                (0.0, 0.0), (object.width, 0.0), (object.width, object.height), (0.0, object.height),
+            ])),
+            ObjectShape::Polygon(p) => WorldShape::Polygon(points(p)),
+            ObjectShape::Polyline(p) => WorldShape::Polyline(points(p)),
+            ObjectShape::Point => WorldShape::Point(transform.apply((0.0, 0.0))),
+            ObjectShape::Ellipse => {
+                let (ax, ay) = transform.apply_vector((object.width / 2.0, 0.0));
+                let (bx, by) = transform.apply_vector((0.0, object.height / 2.0));
+                WorldShape::Ellipse {
+                    center: transform.apply((object.width / 2.0, object.height / 2.0)),
+                    radiusx: ax.hypot(ay),
+                    radiusy: bx.hypot(by),
+                    rotation: ay.atan2(ax).to_degrees(),
+                }
+            },
+            ObjectShape::Text(_) => continue,
+        };
+
+        colliders.push(Collider { gid: gid.id(), source, object, shape });
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{Map, Orientation, StaggerAxis, StaggerIndex};
+
+/// The measures of a staggered or hexagonal grid, the way Tiled derives them from the map.
+/// Staggered maps are hexagonal ones whose sides have no length, their tiles are diamonds.
+/// The measures are widened to `i64`, so no size the map can hold overflows them.
+struct HexParams {
+    tilewidth: i64,
+    tileheight: i64,
+    sidelengthx: i64,
+    sidelengthy: i64,
+    sideoffsetx: i64,
+    sideoffsety: i64,
+    columnwidth: i64,
+    rowheight: i64,
+    staggerx: bool,
+    staggereven: bool,
+}
+
+impl HexParams {
+    fn new(map: &Map) -> HexParams {
+        // Odd sizes can't be split in halves of whole pixels.
+        let tilewidth = i64::from(map.tilewidth & !1);
+        let tileheight = i64::from(map.tileheight & !1);
+        let sidelength = if map.orientation == Orientation::Hexagonal { i64::from(map.hexsidelength) } else { 0 };
+        let staggerx = map.staggeraxis == StaggerAxis::X;
+
+        let sidelengthx = if staggerx { sidelength } else { 0 };
+        let sidelengthy = if staggerx { 0 } else { sidelength };
+        let sideoffsetx = (tilewidth - sidelengthx) / 2;
+        let sideoffsety = (tileheight - sidelengthy) / 2;
+
+        HexParams {
+            tilewidth,
+            tileheight,
+            sidelengthx,
+            sidelengthy,
+            sideoffsetx,
+            sideoffsety,
+            columnwidth: sideoffsetx + sidelengthx,
+            rowheight: sideoffsety + sidelengthy,
+            staggerx,
+            staggereven: map.staggerindex == StaggerIndex::Even,
+        }
+    }
+
+    /// Whether the column or row `index` along the stagger axis is shifted by half a tile.
+    fn is_staggered(&self, index: i64) -> bool {
+        (index & 1 == 1) != self.staggereven
+    }
+
+    fn tile_to_pixel(&self, x: i32, y: i32) -> (i64, i64) {
+        let (x, y) = (i64::from(x), i64::from(y));
+        if self.staggerx {
+            let shift = if self.is_staggered(x) { self.rowheight } else { 0 };
+            (x * self.columnwidth, y * (self.tileheight + self.sidelengthy) + shift)
+        } else {
+            let shift = if self.is_staggered(y) { self.columnwidth } else { 0 };
+            (x * (self.tilewidth + self.sidelengthx) + shift, y * self.rowheight)
+        }
+    }
+
+    /// The four tiles touching the diagonal sides of a tile, clockwise starting at the top right one.
+    fn diagonal_neighbours(&self, x: i32, y: i32) -> [(i32, i32); 4] {
+        if self.staggerx {
+            let top = if self.is_staggered(i64::from(x)) { y } else { y.saturating_sub(1) };
+            let (left, right, bottom) = (x.saturating_sub(1), x.saturating_add(1), top.saturating_add(1));
+            [(right, top), (right, bottom), (left, bottom), (left, top)]
+        } else {
+            let left = if self.is_staggered(i64::from(y)) { x } else { x.saturating_sub(1) };
+            let (right, top, bottom) = (left.saturating_add(1), y.saturating_sub(1), y.saturating_add(1));
+            [(right, top), (right, bottom), (left, bottom), (left, top)]
+        }
+    }
+
+    fn hexagonal_pixel_to_tile(&self, x: f32, y: f32) -> (i32, i32) {
+        let (mut x, mut y) = (x, y);
+        if self.staggerx {
+            x -= if self.staggereven { self.tilewidth } else { self.sideoffsetx } as f32;
+        } else {
+            y -= if self.staggereven { self.tileheight } else { self.sideoffsety } as f32;
+        }
+
+        // The grid repeats every two columns or rows, start with the block the point is in.
+        let blockwidth = (self.columnwidth * 2) as f32;
+        let blockheight = (self.rowheight * 2) as f32;
+        let (mut referencex, mut referencey) = ((x / blockwidth).floor() as i32, (y / blockheight).floor() as i32);
+        let relx = x - referencex as f32 * blockwidth;
+        let rely = y - referencey as f32 * blockheight;
+
+        let index = if self.staggerx { &mut referencex } else { &mut referencey };
+        *index = index.saturating_mul(2).saturating_add(if self.staggereven { 1 } else { 0 });
+
+        // The point belongs to the tile whose center is closest.
+        let (centers, offsets) = if self.staggerx {
+            let left = (self.sidelengthx / 2) as f32;
+            let centerx = left + self.columnwidth as f32;
+            let centery = (self.tileheight / 2) as f32;
+            let (column, row) = (self.columnwidth as f32, self.rowheight as f32);
+            ([(left, centery), (centerx, centery - row), (centerx, centery + row), (centerx + column, centery)],
+             [(0, 0), (1, -1), (1, 0), (2, 0)])
+        } else {
+            let top = (self.sidelengthy / 2) as f32;
+            let centerx = (self.tilewidth / 2) as f32;
+            let centery = top + self.rowheight as f32;
+            let (column, row) = (self.columnwidth as f32, self.rowheight as f32);
+            ([(centerx, top), (centerx - column, centery), (centerx + column, centery), (centerx, centery + row)],
+             [(0, 0), (-1, 1), (0, 1), (0, 2)])
+        };
+
+        let distance = |(cx, cy): (f32, f32)| (cx - relx) * (cx - relx) + (cy - rely) * (cy - rely);
+        let mut nearest = 0;
+        for i in 1..4 {
+            if distance(centers[i]) < distance(centers[nearest]) {
+                nearest = i;
+            }
+        }
+
+        (referencex.saturating_add(offsets[nearest].0), referencey.saturating_add(offsets[nearest].1))
+    }
+
+    fn staggered_pixel_to_tile(&self, x: f32, y: f32) -> (i32, i32) {
+        let (mut x, mut y) = (x, y);
+        if self.staggerx {
+            x -= if self.staggereven { self.sideoffsetx } else { 0 } as f32;
+        } else {
+            y -= if self.staggereven { self.sideoffsety } else { 0 } as f32;
+        }
+
+        // Start with the unshifted tile whose bounding box holds the point, the corners belong to its neighbours.
+        let (width, height) = (self.tilewidth as f32, self.tileheight as f32);
+        let (mut referencex, mut referencey) = ((x / width).floor() as i32, (y / height).floor() as i32);
+        let relx = x - referencex as f32 * width;
+        let rely = y - referencey as f32 * height;
+
+        let index = if self.staggerx { &mut referencex } else { &mut referencey };
+        *index = index.saturating_mul(2).saturating_add(if self.staggereven { 1 } else { 0 });
+
+        let [top_right, bottom_right, bottom_left, top_left] = self.diagonal_neighbours(referencex, referencey);
+        let edge = relx * height / width;
+        let half = self.sideoffsety as f32;
+
+        if half - edge > rely {
+            top_left
+        } else if edge - half > rely {
+            top_right
+        } else if half + edge < rely {
+            bottom_left
+        } else if half * 3.0 - edge < rely {
+            bottom_right
+        } else {
+            (referencex, referencey)
+        }
+    }
+}
+
+impl Map {
+    /// Returns the top left corner of the bounding box of a tile, in pixels.
+    ///
+    /// Isometric maps start with the top corner of the tile at 0, 0 half a tile from the left edge of the map.
+    /// Tiles are drawn aligned to the bottom left corner of their bounding box.
+    pub fn tile_to_pixel(&self, x: i32, y: i32) -> (f32, f32) {
+        match self.orientation {
+            Orientation::Orthogonal => ((wide(x) * wide(self.tilewidth)) as f32, (wide(y) * wide(self.tileheight)) as f32),
+            Orientation::Isometric => {
+                let originx = (wide(self.height) * wide(self.tilewidth)) as f32 / 2.0;
+                let top = (wide(x) - wide(y)) as f32 * self.tilewidth as f32 / 2.0 + originx;
+                (top - self.tilewidth as f32 / 2.0, (wide(x) + wide(y)) as f32 * self.tileheight as f32 / 2.0)
+            },
+            Orientation::Staggered | Orientation::Hexagonal => {
+                let (px, py) = HexParams::new(self).tile_to_pixel(x, y);
+                (px as f32, py as f32)
+            },
+        }
+    }
+
+    /// Returns the tile whose shape holds the given pixel. Pixels beyond the map return tiles beyond it.
+    pub fn pixel_to_tile(&self, x: f32, y: f32) -> (i32, i32) {
+        match self.orientation {
+            Orientation::Orthogonal => ((x / self.tilewidth as f32).floor() as i32, (y / self.tileheight as f32).floor() as i32),
+            Orientation::Isometric => {
+                let originx = (wide(self.height) * wide(self.tilewidth)) as f32 / 2.0;
+                let tilex = (x - originx) / self.tilewidth as f32;
+                let tiley = y / self.tileheight as f32;
+                ((tiley + tilex).floor() as i32, (tiley - tilex).floor() as i32)
+            },
+            Orientation::Staggered => HexParams::new(self).staggered_pixel_to_tile(x, y),
+            Orientation::Hexagonal => HexParams::new(self).hexagonal_pixel_to_tile(x, y),
+        }
+    }
+
+    /// Returns the outline of a tile in pixels, clockwise. Rectangles and diamonds have four corners, hexagons six.
+    pub fn tile_polygon(&self, x: i32, y: i32) -> Vec<(f32, f32)> {
+        let (left, top) = self.tile_to_pixel(x, y);
+
+        let points: Vec<(f32, f32)> = match self.orientation {
+            Orientation::Orthogonal => {
+                let (width, height) = (self.tilewidth as f32, self.tileheight as f32);
+                vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            },
+            Orientation::Isometric => {
+                let (width, height) = (self.tilewidth as f32, self.tileheight as f32);
+                vec![(width / 2.0, 0.0), (width, height / 2.0), (width / 2.0, height), (0.0, height / 2.0)]
+            },
+            Orientation::Staggered | Orientation::Hexagonal => {
+                let p = HexParams::new(self);
+                let mut points: Vec<(i64, i64)> = vec![
+                    (p.sideoffsetx, 0),
+                    (p.tilewidth - p.sideoffsetx, 0),
+                    (p.tilewidth, p.sideoffsety),
+                    (p.tilewidth, p.tileheight - p.sideoffsety),
+                    (p.tilewidth - p.sideoffsetx, p.tileheight),
+                    (p.sideoffsetx, p.tileheight),
+                    (0, p.tileheight - p.sideoffsety),
+                    (0, p.sideoffsety),
+                ];
+                // Sides without length leave the same corner twice.
+                points.dedup();
+                points.into_iter().map(|(px, py)| (px as f32, py as f32)).collect()
+            },
+        };
+
+        points.into_iter().map(|(px, py)| (left + px, top + py)).collect()
+    }
+
+    /// Returns the tiles sharing a side with the given one, clockwise starting at the top.
+    /// Tiles beyond the edges of the map are included, down to the limits of `i32` where the coordinates stop.
+    pub fn neighbours(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        let (left, right, top, bottom) = (x.saturating_sub(1), x.saturating_add(1), y.saturating_sub(1), y.saturating_add(1));
+        match self.orientation {
+            Orientation::Orthogonal => vec![(x, top), (right, y), (x, bottom), (left, y)],
+            // The sides of the diamond face up right, down right, down left and up left.
+            Orientation::Isometric => vec![(x, top), (right, y), (x, bottom), (left, y)],
+            Orientation::Staggered => HexParams::new(self).diagonal_neighbours(x, y).to_vec(),
+            Orientation::Hexagonal => {
+                let p = HexParams::new(self);
+                let [top_right, bottom_right, bottom_left, top_left] = p.diagonal_neighbours(x, y);
+                if p.staggerx {
+                    vec![(x, top), top_right, bottom_right, (x, bottom), bottom_left, top_left]
+                } else {
+                    vec![top_right, (right, y), bottom_right, bottom_left, (left, y), top_left]
+                }
+            },
+        }
+    }
+}
+
+/// Widens a coordinate or size, so products and sums of them can't overflow.
+fn wide(value: i32) -> i64 {
+    i64::from(value)
+}
@@ -0,0 +1,187 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use std::io::{self, Read};
+#[cfg(any(feature = "zlib", feature = "gzip"))]
+use std::io::Write;
+
+use crate::{Compression, Gid, TmxError};
+
+/// Decodes the content of a `<data encoding="csv">` element.
+/// The gids are counted first, so large layers are decoded without growing the result again and again.
+pub(crate) fn decode_csv(text: &str) -> Result<Vec<Gid>, TmxError> {
+    let mut data = Vec::with_capacity(text.bytes().filter(|b| *b == b',').count() + 1);
+
+    for s in text.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        data.push(s.parse::<u32>().map(Gid).map_err(|_| TmxError::InvalidTileData(format!("Invalid gid in csv data: {}", s)))?);
+    }
+
+    Ok(data)
+}
+
+/// Decodes the content of a `<data encoding="base64">` element, inflating it first if a compression is given.
+pub(crate) fn decode_base64(text: &str, compression: Option<Compression>) -> Result<Vec<Gid>, TmxError> {
+    decode_bytes(decode_base64_bytes(text)?, compression)
+}
+
+/// Decodes base64 tile data without inflating it, so it can be decoded before the compression is known.
+pub(crate) fn decode_base64_bytes(text: &str) -> Result<Vec<u8>, TmxError> {
+    let bytes = if text.contains(char::is_whitespace) {
+        let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+        base64::read::DecoderReader::new(SkipWhitespace(text.as_bytes()), &STANDARD).read_to_end(&mut bytes)
+            .map(|_| bytes)
+            .map_err(|e| e.to_string())
+    } else {
+        STANDARD.decode(text).map_err(|e| e.to_string())
+    };
+    bytes.map_err(|e| TmxError::InvalidTileData(format!("Invalid base64 data: {}", e)))
+}
+
+/// Reads the gids out of decoded tile data, inflating it first if a compression is given.
+pub(crate) fn decode_bytes(bytes: Vec<u8>, compression: Option<Compression>) -> Result<Vec<Gid>, TmxError> {
+    let bytes = match compression {
+        None => bytes,
+        Some(compression) => decompress(&bytes, compression)?,
+    };
+
+    gids_from_bytes(&bytes)
+}
+
+/// Reads base64 text without the line breaks and indentation it is wrapped with, so it doesn't have to be copied first.
+struct SkipWhitespace<'a>(&'a [u8]);
+
+impl Read for SkipWhitespace<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while let (Some((byte, rest)), true) = (self.0.split_first(), read < buf.len()) {
+            if !byte.is_ascii_whitespace() {
+                buf[read] = *byte;
+                read += 1;
+            }
+            self.0 = rest;
+        }
+        Ok(read)
+    }
+}
+
+/// Every gid is stored as an unsigned 32 bit little-endian integer.
+fn gids_from_bytes(bytes: &[u8]) -> Result<Vec<Gid>, TmxError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(TmxError::InvalidTileData(format!("Tile data length {} is not a multiple of 4", bytes.len())));
+    }
+
+    Ok(bytes.chunks(4)
+        .map(|c| Gid(u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+        .collect())
+}
+
+#[cfg(any(feature = "zlib", feature = "gzip", feature = "zstd"))]
+fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, TmxError> {
+    let mut inflated = Vec::new();
+
+    match compression {
+        #[cfg(feature = "zlib")]
+        Compression::Zlib => {
+            flate2::read::ZlibDecoder::new(bytes).read_to_end(&mut inflated)
+                .map_err(|e| TmxError::InvalidTileData(format!("Invalid zlib data: {}", e)))?;
+        },
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut inflated)
+                .map_err(|e| TmxError::InvalidTileData(format!("Invalid gzip data: {}", e)))?;
+        },
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            ruzstd::decoding::StreamingDecoder::new(bytes)
+                .map_err(|e| TmxError::InvalidTileData(format!("Invalid zstd data: {}", e)))?
+                .read_to_end(&mut inflated)
+                .map_err(|e| TmxError::InvalidTileData(format!("Invalid zstd data: {}", e)))?;
+        },
+        #[cfg(not(feature = "zlib"))]
+        Compression::Zlib => return Err(unsupported(compression)),
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => return Err(unsupported(compression)),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err(unsupported(compression)),
+    }
+
+    Ok(inflated)
+}
+
+#[cfg(not(any(feature = "zlib", feature = "gzip", feature = "zstd")))]
+fn decompress(_bytes: &[u8], compression: Compression) -> Result<Vec<u8>, TmxError> {
+    Err(unsupported(compression))
+}
+
+/// Encodes tiles as the content of a `<data encoding="csv">` element, one row per line.
+pub(crate) fn encode_csv(data: &[Gid], width: i32) -> String {
+    let rows: Vec<String> = data.chunks(width.max(1) as usize)
+        .map(|row| row.iter().map(|gid| gid.to_string()).collect::<Vec<String>>().join(","))
+        .collect();
+
+    format!("\n{}\n", rows.join(",\n"))
+}
+
+/// Encodes tiles as the content of a `<data encoding="base64">` element, compressing them first if a compression is given.
+pub(crate) fn encode_base64(data: &[Gid], compression: Option<Compression>) -> Result<String, TmxError> {
+    let bytes: Vec<u8> = data.iter()
+        .flat_map(|gid| gid.0.to_le_bytes())
+        .collect();
+
+    let bytes = match compression {
+        None => bytes,
+        Some(compression) => compress(&bytes, compression)?,
+    };
+
+    Ok(STANDARD.encode(bytes))
+}
+
+#[cfg(any(feature = "zlib", feature = "gzip", feature = "zstd"))]
+fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, TmxError> {
+    #[cfg(any(feature = "zlib", feature = "gzip"))]
+    let failed = |e: std::io::Error| TmxError::InvalidTileData(format!("Unable to compress tile data: {}", e));
+
+    match compression {
+        #[cfg(feature = "zlib")]
+        Compression::Zlib => {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes).map_err(failed)?;
+            encoder.finish().map_err(failed)
+        },
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes).map_err(failed)?;
+            encoder.finish().map_err(failed)
+        },
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(ruzstd::encoding::compress_to_vec(bytes, ruzstd::encoding::CompressionLevel::Fastest)),
+        #[cfg(not(feature = "zlib"))]
+        Compression::Zlib => Err(unsupported(compression)),
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => Err(unsupported(compression)),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(unsupported(compression)),
+    }
+}
+
+#[cfg(not(any(feature = "zlib", feature = "gzip", feature = "zstd")))]
+fn compress(_bytes: &[u8], compression: Compression) -> Result<Vec<u8>, TmxError> {
+    Err(unsupported(compression))
+}
+
+/// The feature of the compression is disabled.
+#[cfg_attr(all(feature = "zlib", feature = "gzip", feature = "zstd"), allow(dead_code))]
+fn unsupported(compression: Compression) -> TmxError {
+    let value = match compression {
+        Compression::Zlib => "zlib",
+        Compression::Gzip => "gzip",
+        Compression::Zstd => "zstd",
+    };
+
+    TmxError::UnknownValue {
+        element: "data".to_string(),
+        attribute: "compression".to_string(),
+        value: value.to_string(),
+    }
+}
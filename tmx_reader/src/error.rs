@@ -0,0 +1,68 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while reading a map.
+#[derive(Debug)]
+pub enum TmxError {
+    /// The document isn't well-formed, `position` is the byte offset the reader had reached.
+    Xml { position: usize, error: quick_xml::Error },
+    /// A required attribute is missing from an element.
+    MissingAttribute { element: String, attribute: String },
+    /// An attribute that has to be a number can't be parsed as one.
+    InvalidNumber { element: String, attribute: String, value: String },
+    /// An attribute holds a value outside of the ones the format allows, e.g. an unknown orientation.
+    UnknownValue { element: String, attribute: String, value: String },
+    /// A required element is missing, e.g. a document without `<map>`.
+    MissingElement(String),
+    /// The content of a `<data>` element can't be decoded.
+    InvalidTileData(String),
+    /// A referenced file couldn't be loaded through the `ResourceLoader`.
+    Resource { path: String, error: io::Error },
+    /// A `.tiled-project` file can't be parsed or defines invalid property types.
+    InvalidProject(String),
+    /// A `.tmj`, `.tsj` or `.tj` document isn't valid JSON or doesn't have the structure Tiled writes.
+    InvalidJson(String),
+    /// A `.world` file isn't valid JSON or has an invalid pattern.
+    InvalidWorld(String),
+    /// Writing a map failed, usually because the underlying writer did.
+    Write(quick_xml::Error),
+}
+
+impl fmt::Display for TmxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TmxError::Xml { position, error } =>
+                write!(f, "XML error at position {}: {}", position, error),
+            TmxError::MissingAttribute { element, attribute } =>
+                write!(f, "Missing attribute '{}' on <{}>", attribute, element),
+            TmxError::InvalidNumber { element, attribute, value } =>
+                write!(f, "Attribute '{}' on <{}> is not a valid number: '{}'", attribute, element, value),
+            TmxError::UnknownValue { element, attribute, value } =>
+                write!(f, "Unknown value for attribute '{}' on <{}>: '{}'", attribute, element, value),
+            TmxError::MissingElement(element) =>
+                write!(f, "Missing element <{}>", element),
+            TmxError::InvalidTileData(message) =>
+                write!(f, "Invalid tile data: {}", message),
+            TmxError::Resource { path, error } =>
+                write!(f, "Unable to load '{}': {}", path, error),
+            TmxError::InvalidProject(message) =>
+                write!(f, "Invalid project: {}", message),
+            TmxError::InvalidJson(message) =>
+                write!(f, "Invalid JSON: {}", message),
+            TmxError::InvalidWorld(message) =>
+                write!(f, "Invalid world: {}", message),
+            TmxError::Write(error) =>
+                write!(f, "Unable to write map: {}", error),
+        }
+    }
+}
+
+impl error::Error for TmxError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TmxError::Resource { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+/// A global tile id as stored in tile layers and tile objects, the upper bits hold how the tile is flipped.
+///
+/// 0 is an empty cell. `id` is the tile id the tilesets are looked up with, see `Map::tile_for_gid`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Gid(pub u32);
+
+/// The flip flags stored in the upper bits of a gid.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlipFlags {
+    pub horizontally: bool,
+    pub vertically: bool,
+    /// Swaps x and y, which combined with the other flags rotates the tile by 90 degrees.
+    pub diagonally: bool,
+    /// Rotates the tile by 120 degrees clockwise, only used in hexagonal maps.
+    pub rotated_hexagonal_120: bool,
+}
+
+impl Gid {
+    pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+    pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+    pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+    pub const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+    /// Every bit that isn't part of the id.
+    pub const FLAGS: u32 = Gid::FLIPPED_HORIZONTALLY | Gid::FLIPPED_VERTICALLY | Gid::FLIPPED_DIAGONALLY | Gid::ROTATED_HEXAGONAL_120;
+
+    /// The highest id the bits below the flags can hold.
+    pub const MAX_ID: i64 = (!Gid::FLAGS) as i64;
+
+    pub const EMPTY: Gid = Gid(0);
+
+    /// Combines a tile id with flip flags.
+    ///
+    /// `None` if `id` is negative or above `Gid::MAX_ID`, its upper bits would be taken for flags otherwise.
+    pub fn new(id: i64, flags: FlipFlags) -> Option<Gid> {
+        if !(0..=Gid::MAX_ID).contains(&id) {
+            return None;
+        }
+
+        let flag = |set: bool, bit: u32| if set { bit } else { 0 };
+        Some(Gid(id as u32
+            | flag(flags.horizontally, Gid::FLIPPED_HORIZONTALLY)
+            | flag(flags.vertically, Gid::FLIPPED_VERTICALLY)
+            | flag(flags.diagonally, Gid::FLIPPED_DIAGONALLY)
+            | flag(flags.rotated_hexagonal_120, Gid::ROTATED_HEXAGONAL_120)))
+    }
+
+    /// The global tile id with the flags cleared.
+    pub fn id(self) -> i64 {
+        i64::from(self.0 & !Gid::FLAGS)
+    }
+
+    /// Replaces the id, keeping the flags. `None` for ids `Gid::new` rejects.
+    pub fn with_id(self, id: i64) -> Option<Gid> {
+        Gid::new(id, self.flip_flags())
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.id() == 0
+    }
+
+    pub fn flipped_horizontally(self) -> bool {
+        self.0 & Gid::FLIPPED_HORIZONTALLY != 0
+    }
+
+    pub fn flipped_vertically(self) -> bool {
+        self.0 & Gid::FLIPPED_VERTICALLY != 0
+    }
+
+    pub fn flipped_diagonally(self) -> bool {
+        self.0 & Gid::FLIPPED_DIAGONALLY != 0
+    }
+
+    pub fn rotated_hexagonal_120(self) -> bool {
+        self.0 & Gid::ROTATED_HEXAGONAL_120 != 0
+    }
+
+    pub fn flip_flags(self) -> FlipFlags {
+        FlipFlags {
+            horizontally: self.flipped_horizontally(),
+            vertically: self.flipped_vertically(),
+            diagonally: self.flipped_diagonally(),
+            rotated_hexagonal_120: self.rotated_hexagonal_120(),
+        }
+    }
+
+    /// How the tile is drawn, relative to its center.
+    ///
+    /// Tiled applies the diagonal flip first, then the horizontal and vertical ones and rotates last.
+    /// Maps a point of the tile image, relative to the center of the image, to where it ends up relative to the
+    /// center of the drawn tile. A diagonal flip swaps the width and height of the tile.
+    pub fn transform(self) -> Affine {
+        let mut transform = Affine::IDENTITY;
+
+        if self.flipped_diagonally() {
+            transform = transform.then(Affine { a: 0.0, b: 1.0, c: 1.0, d: 0.0, e: 0.0, f: 0.0 });
+        }
+        if self.flipped_horizontally() {
+            transform = transform.then(Affine { a: -1.0, ..Affine::IDENTITY });
+        }
+        if self.flipped_vertically() {
+            transform = transform.then(Affine { d: -1.0, ..Affine::IDENTITY });
+        }
+        if self.rotated_hexagonal_120() {
+            transform = transform.then(Affine::rotate(120.0));
+        }
+
+        transform
+    }
+
+    /// Like `transform`, but relative to the top left corners of a tile image of the given size and of the drawn tile.
+    /// Also returns the size of the drawn tile.
+    pub fn transform_within(self, width: f32, height: f32) -> (Affine, f32, f32) {
+        let (drawnwidth, drawnheight) = if self.flipped_diagonally() { (height, width) } else { (width, height) };
+
+        let transform = Affine::translate(-width / 2.0, -height / 2.0)
+            .then(self.transform())
+            .then(Affine::translate(drawnwidth / 2.0, drawnheight / 2.0));
+
+        (transform, drawnwidth, drawnheight)
+    }
+}
+
+/// The raw value, flags included, the way TMX stores it.
+impl fmt::Display for Gid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A 2D affine transformation, maps `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f32, y: f32) -> Affine {
+        Affine { e: x, f: y, ..Affine::IDENTITY }
+    }
+
+    /// Rotates clockwise around the origin, y points down.
+    pub fn rotate(degrees: f32) -> Affine {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Affine { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// Scales something of size `width` x `height` to `target_width` x `target_height`, a size of 0 keeps it as is.
+    pub(crate) fn scale_by(self, width: f32, height: f32, target_width: f32, target_height: f32) -> Affine {
+        let factor = |size: f32, target: f32| if size == 0.0 || target == 0.0 { 1.0 } else { target / size };
+        self.then(Affine { a: factor(width, target_width), d: factor(height, target_height), ..Affine::IDENTITY })
+    }
+
+    /// Applies `self` first and `next` afterwards.
+    pub fn then(self, next: Affine) -> Affine {
+        Affine {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// Applies the transformation without the translation.
+    pub fn apply_vector(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+
+    /// The transformation as a row-major 3x3 matrix, for renderers working with homogeneous coordinates.
+    pub fn to_matrix(&self) -> [[f32; 3]; 3] {
+        [[self.a, self.c, self.e], [self.b, self.d, self.f], [0.0, 0.0, 1.0]]
+    }
+}
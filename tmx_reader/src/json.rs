@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Read;
+use std::mem;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use serde_json::Value;
+
+use crate::encoding;
+use crate::loader::NoLoader;
+use crate::{Chunk, Compression, DataEncoding, Frame, Gid, Image, LayerKind, Layer, Map, Object, ObjectShape, Objectgroup,
+            Properties, Property, PropertyValue, ResourceLoader, TerrainType, Tile, Tileset, TmxError, WangColor,
+            WangSet, WangTile};
+use crate::{Attributes, Context, Template};
+use crate::{chunk_from_attributes, compression_from_string, encoding_from_string, frame_from_attributes,
+            grid_from_attributes, group_from_attributes, imagelayer_from_attributes, join_path,
+            layer_from_attributes, load_tileset, map_from_attributes, object_with_template,
+            objectgroup_from_attributes, property_from_attributes, template_from_object, text_from_attributes,
+            terraintype_from_attributes, tile_from_attributes, tileoffset_from_attributes, tileset_from_attributes,
+            transformations_from_attributes, wangcolor_from_attributes, wangset_from_attributes,
+            wangtile_from_attributes};
+
+impl Map {
+    /// Parses a map saved as JSON (`.tmj`), external tilesets and templates can't be resolved.
+    pub fn parse_json(json: &str) -> Result<Map, TmxError> {
+        Map::parse_json_with_loader(json, &NoLoader)
+    }
+
+    /// Parses a map saved as JSON whose external tilesets and templates are loaded through the given loader.
+    /// Those may be saved as JSON or XML.
+    pub fn parse_json_with_loader(json: &str, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        let (root, mut tile_data) = parse_map(&mut serde_json::Deserializer::from_str(json.trim_start_matches('\u{feff}')))?;
+        read_map(&root, &mut tile_data, loader)
+    }
+
+    /// Like `parse_json_with_loader`, but the JSON is parsed while it is read instead of being read into a string first.
+    pub(crate) fn from_json_reader_with_loader<R: Read>(source: R, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        let (root, mut tile_data) = parse_map(&mut serde_json::Deserializer::from_reader(source))?;
+        read_map(&root, &mut tile_data, loader)
+    }
+}
+
+/// Reads the `.tsj` file of an external tileset.
+pub(crate) fn read_external_tileset(json: &str, source: &str, firstgid: i64, loader: &dyn ResourceLoader) -> Result<Tileset, TmxError> {
+    let mut tileset = read_tileset(&parse(json)?, firstgid, &mut Context::new(loader))?;
+    tileset.source = Some(source.to_string());
+    Ok(tileset)
+}
+
+/// Reads the `.tj` file of a template, the tileset it references is relative to the template itself.
+pub(crate) fn read_template(json: &str, path: &str, context: &mut Context) -> Result<Template, TmxError> {
+    let root = parse(json)?;
+
+    let tileset = match &root["tileset"] {
+        Value::Null => None,
+        tileset => {
+            let attrs = attributes(tileset, "tileset")?;
+            Some((attrs.required_number("firstgid")?, join_path(path, attrs.required("source")?)))
+        },
+    };
+
+    let object = &root["object"];
+    if object.is_null() {
+        return Err(TmxError::MissingElement("object".to_string()));
+    }
+
+    template_from_object(object_attributes(object)?, properties(object)?, shape(object)?, tileset.as_ref(), context)
+}
+
+fn parse(json: &str) -> Result<Value, TmxError> {
+    serde_json::from_str(json.trim_start_matches('\u{feff}'))
+        .map_err(|e| TmxError::InvalidJson(e.to_string()))
+}
+
+/// Parses a map, the tile data of its layers and chunks ends up in `TileData`.
+fn parse_map<'de, R: serde_json::de::Read<'de>>(deserializer: &mut serde_json::Deserializer<R>) -> Result<(Value, TileData), TmxError> {
+    let mut tile_data = TileData::default();
+    let root = Seed { level: Level::Map, tile_data: &mut tile_data }.deserialize(&mut *deserializer)
+        .and_then(|root| deserializer.end().map(|_| root))
+        .map_err(|e| TmxError::InvalidJson(e.to_string()))?;
+    Ok((root, tile_data))
+}
+
+fn read_map(root: &Value, tile_data: &mut TileData, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+    let attrs = attributes(root, "map")?;
+
+    let mut context = Context::new(loader);
+    context.infinite = attrs.bool_or("infinite", false)?;
+
+    for tileset in array(root, "tilesets")? {
+        let tileset_attrs = attributes(tileset, "tileset")?;
+        let firstgid = tileset_attrs.required_number("firstgid")?;
+
+        let tileset = match tileset_attrs.get("source") {
+            Some(source) => load_tileset(source, firstgid, loader)?,
+            None => read_tileset(tileset, firstgid, &mut context)?,
+        };
+        context.tilesets.push(tileset);
+    }
+
+    let layers = read_layers(root, tile_data, &mut context)?;
+
+    map_from_attributes(&attrs, context.tilesets, layers, properties(root)?)
+}
+
+/// Images are stored as members of the tileset, tile or image layer that shows them.
+fn read_image(attrs: &Attributes) -> Result<Option<Image>, TmxError> {
+    match attrs.get("image") {
+        None => Ok(None),
+        Some(source) => Ok(Some(Image {
+            format: None,
+            source: source.to_string(),
+            trans: attrs.get("transparentcolor").map(|color| color.trim_start_matches('#').to_string()),
+            width: attrs.required_number("imagewidth")?,
+            height: attrs.number("imageheight")?,
+        })),
+    }
+}
+
+fn read_tileset(value: &Value, firstgid: i64, context: &mut Context) -> Result<Tileset, TmxError> {
+    let attrs = attributes(value, "tileset")?;
+
+    Ok(Tileset {
+        image: read_image(&attrs)?,
+        tiles: array(value, "tiles")?.iter()
+            .map(|tile| read_tile(tile, context))
+            .collect::<Result<Vec<Tile>, TmxError>>()?,
+        tileoffset: nested(value, "tileoffset")?.map(|attrs| tileoffset_from_attributes(&attrs)).transpose()?,
+        grid: nested(value, "grid")?.map(|attrs| grid_from_attributes(&attrs)).transpose()?,
+        transformations: nested(value, "transformations")?.map(|attrs| transformations_from_attributes(&attrs)).transpose()?,
+        wangsets: array(value, "wangsets")?.iter()
+            .map(read_wangset)
+            .collect::<Result<Vec<WangSet>, TmxError>>()?,
+        terraintypes: array(value, "terrains")?.iter()
+            .map(|terrain| terraintype_from_attributes(&attributes(terrain, "terrain")?, properties(terrain)?))
+            .collect::<Result<Vec<TerrainType>, TmxError>>()?,
+        properties: properties(value)?,
+        ..tileset_from_attributes(&attrs, firstgid)?
+    })
+}
+
+fn read_wangset(value: &Value) -> Result<WangSet, TmxError> {
+    let colors = array(value, "colors")?.iter()
+        .map(|color| wangcolor_from_attributes(&attributes(color, "wangcolor")?, properties(color)?))
+        .collect::<Result<Vec<WangColor>, TmxError>>()?;
+
+    let tiles = array(value, "wangtiles")?.iter()
+        .map(|tile| {
+            let mut attrs = attributes(tile, "wangtile")?;
+            let wangid: Vec<String> = array(tile, "wangid")?.iter().map(|color| color.to_string()).collect();
+            attrs.values.insert("wangid".to_string(), wangid.join(","));
+            wangtile_from_attributes(&attrs)
+        })
+        .collect::<Result<Vec<WangTile>, TmxError>>()?;
+
+    wangset_from_attributes(&attributes(value, "wangset")?, colors, tiles, properties(value)?)
+}
+
+fn read_tile(value: &Value, context: &mut Context) -> Result<Tile, TmxError> {
+    let mut attrs = attributes(value, "tile")?;
+
+    // The terrain of each corner, -1 where there is none. XML leaves those corners empty.
+    if let Value::Array(corners) = &value["terrain"] {
+        let terrain: Vec<String> = corners.iter()
+            .map(|corner| corner.as_i64().filter(|t| *t >= 0).map(|t| t.to_string()).unwrap_or_default())
+            .collect();
+        attrs.values.insert("terrain".to_string(), terrain.join(","));
+    }
+
+    let animation = array(value, "animation")?.iter()
+        .map(|frame| frame_from_attributes(&attributes(frame, "frame")?))
+        .collect::<Result<Vec<Frame>, TmxError>>()?;
+
+    let collision = match &value["objectgroup"] {
+        Value::Null => None,
+        objectgroup => Some(read_objectgroup(objectgroup, context)?),
+    };
+
+    tile_from_attributes(&attrs, read_image(&attrs)?, properties(value)?, animation, collision)
+}
+
+fn read_layers(value: &Value, tile_data: &mut TileData, context: &mut Context) -> Result<Vec<LayerKind>, TmxError> {
+    let mut layers = Vec::new();
+    for layer in array(value, "layers")? {
+        layers.extend(read_layer_kind(layer, tile_data, context)?);
+    }
+    Ok(layers)
+}
+
+/// Reads any kind of layer, returns `None` for unknown kinds like the XML reader does for unknown elements.
+fn read_layer_kind(value: &Value, tile_data: &mut TileData, context: &mut Context) -> Result<Option<LayerKind>, TmxError> {
+    Ok(Some(match value["type"].as_str() {
+        Some("tilelayer")   => LayerKind::Tile(read_layer(value, tile_data, context.infinite)?),
+        Some("objectgroup") => LayerKind::Object(read_objectgroup(value, context)?),
+        Some("imagelayer")  => {
+            let attrs = attributes(value, "imagelayer")?;
+            LayerKind::Image(imagelayer_from_attributes(&attrs, read_image(&attrs)?, properties(value)?)?)
+        },
+        Some("group")       => {
+            let layers = read_layers(value, tile_data, context)?;
+            LayerKind::Group(group_from_attributes(&attributes(value, "group")?, layers, properties(value)?)?)
+        },
+        _                   => return Ok(None),
+    }))
+}
+
+/// Tile data is either an array of gids or a base64 string, the same goes for the data of each chunk.
+fn read_layer(value: &Value, tile_data: &mut TileData, infinite: bool) -> Result<Layer, TmxError> {
+    let attrs = attributes(value, "layer")?;
+    let encoding = attrs.value("encoding", encoding_from_string)?.unwrap_or(DataEncoding::Csv);
+    let compression = attrs.value("compression", compression_from_string)?;
+
+    let data = match &value["data"] {
+        Value::Null => Vec::new(),
+        data => read_tile_data(data, tile_data, compression)?,
+    };
+
+    let chunks = array(value, "chunks")?.iter()
+        .map(|chunk| chunk_from_attributes(&attributes(chunk, "chunk")?, read_tile_data(&chunk["data"], tile_data, compression)?))
+        .collect::<Result<Vec<Chunk>, TmxError>>()?;
+
+    Ok(Layer {
+        properties: properties(value)?,
+        encoding,
+        compression,
+        ..layer_from_attributes(&attrs, data, chunks, infinite)?
+    })
+}
+
+fn read_tile_data(data: &Value, tile_data: &mut TileData, compression: Option<Compression>) -> Result<Vec<Gid>, TmxError> {
+    match data {
+        Value::Array(index) => match index.first().and_then(Value::as_u64).and_then(|index| tile_data.0.get_mut(index as usize)) {
+            Some(tiles) => match mem::replace(tiles, Ok(Tiles::Gids(Vec::new())))? {
+                Tiles::Gids(gids) => Ok(gids),
+                Tiles::Bytes(bytes) => encoding::decode_bytes(bytes, compression),
+            },
+            None => Err(TmxError::InvalidTileData(format!("Unknown tile data: {}", data))),
+        },
+        other => Err(TmxError::InvalidTileData(format!("Expected an array or a base64 string: {}", other))),
+    }
+}
+
+fn read_objectgroup(value: &Value, context: &mut Context) -> Result<Objectgroup, TmxError> {
+    let objects = array(value, "objects")?.iter()
+        .map(|object| read_object(object, context))
+        .collect::<Result<Vec<Object>, TmxError>>()?;
+
+    objectgroup_from_attributes(&attributes(value, "objectgroup")?, objects, properties(value)?)
+}
+
+fn read_object(value: &Value, context: &mut Context) -> Result<Object, TmxError> {
+    object_with_template(object_attributes(value)?, properties(value)?, shape(value)?, context)
+}
+
+/// Tiled 1.9 wrote the type of objects as `class`, the XML reader only knows `type`.
+fn object_attributes(value: &Value) -> Result<Attributes, TmxError> {
+    let mut attrs = attributes(value, "object")?;
+    if let Some(class) = attrs.values.remove("class") {
+        attrs.values.entry("type".to_string()).or_insert(class);
+    }
+    Ok(attrs)
+}
+
+/// `None` if the object doesn't state its shape, it's then a rectangle or taken from its template.
+fn shape(value: &Value) -> Result<Option<ObjectShape>, TmxError> {
+    if value["ellipse"].as_bool() == Some(true) {
+        return Ok(Some(ObjectShape::Ellipse));
+    }
+    if value["point"].as_bool() == Some(true) {
+        return Ok(Some(ObjectShape::Point));
+    }
+    if value["polygon"].is_array() {
+        return Ok(Some(ObjectShape::Polygon(read_points(value, "polygon")?)));
+    }
+    if value["polyline"].is_array() {
+        return Ok(Some(ObjectShape::Polyline(read_points(value, "polyline")?)));
+    }
+    if let text @ Value::Object(_) = &value["text"] {
+        let content = text["text"].as_str().unwrap_or_default().to_string();
+        return Ok(Some(ObjectShape::Text(text_from_attributes(&attributes(text, "text")?, content)?)));
+    }
+    Ok(None)
+}
+
+fn read_points(value: &Value, key: &str) -> Result<Vec<(f32, f32)>, TmxError> {
+    array(value, key)?.iter().map(|point| {
+        let attrs = attributes(point, key)?;
+        Ok((attrs.required_number("x")?, attrs.required_number("y")?))
+    }).collect()
+}
+
+fn properties(value: &Value) -> Result<Properties, TmxError> {
+    array(value, "properties")?.iter()
+        .map(read_property)
+        .collect::<Result<Vec<Property>, TmxError>>()
+        .map(Properties::new)
+}
+
+fn read_property(value: &Value) -> Result<Property, TmxError> {
+    let members = match &value["value"] {
+        Value::Object(members) => Properties::new(members.iter().map(|(name, value)| member(name, value)).collect()),
+        _ => Properties::default(),
+    };
+    let text = value["value"].as_str().unwrap_or_default().to_string();
+
+    property_from_attributes(&attributes(value, "property")?, text, members)
+}
+
+/// Class values only hold the names and values of their members, so the type follows from the JSON value.
+/// `Map::apply_project` converts them to the types the class declares.
+fn member(name: &str, value: &Value) -> Property {
+    Property {
+        name: name.to_string(),
+        propertytype: None,
+        value: match value {
+            Value::Bool(value) => PropertyValue::Bool(*value),
+            Value::Number(number) => match number.as_i64() {
+                Some(value) => PropertyValue::Int(value),
+                None => PropertyValue::Float(number.as_f64().unwrap_or_default()),
+            },
+            Value::String(value) => PropertyValue::String(value.clone()),
+            Value::Object(members) =>
+                PropertyValue::Class(Properties::new(members.iter().map(|(name, value)| member(name, value)).collect())),
+            other => PropertyValue::String(other.to_string()),
+        },
+    }
+}
+
+/// Collects the scalar members of a JSON object, so they can be read like the attributes of an XML element.
+/// Empty strings are left out, in XML Tiled omits those attributes.
+fn attributes(value: &Value, element: &str) -> Result<Attributes, TmxError> {
+    let object = value.as_object()
+        .ok_or_else(|| TmxError::InvalidJson(format!("Expected an object for {}: {}", element, value)))?;
+
+    let mut values = HashMap::new();
+    for (key, value) in object {
+        let value = match value {
+            Value::String(value) if value.is_empty() => continue,
+            Value::String(value) => value.clone(),
+            Value::Number(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            _ => continue,
+        };
+        values.insert(key.to_lowercase(), value);
+    }
+
+    Ok(Attributes { element: element.to_string(), values })
+}
+
+/// The attributes of the object `key` nested in `value`, if there is one.
+fn nested(value: &Value, key: &str) -> Result<Option<Attributes>, TmxError> {
+    match &value[key] {
+        Value::Null => Ok(None),
+        nested => attributes(nested, key).map(Some),
+    }
+}
+
+/// The array `key` of `value`, a missing array is empty.
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], TmxError> {
+    match &value[key] {
+        Value::Null => Ok(&[]),
+        Value::Array(values) => Ok(values),
+        other => Err(TmxError::InvalidJson(format!("Expected an array for {}: {}", key, other))),
+    }
+}
+
+/// The tile data of a map's layers and chunks, taken out of the document while it is parsed. As a `Value` every gid
+/// would take several times the memory its digits take in the file, and base64 text a third more than its bytes.
+///
+/// The document holds a one element array with the index into the tile data in its place, an array can't appear
+/// there otherwise as all of them end up here.
+#[derive(Default)]
+struct TileData(Vec<Result<Tiles, TmxError>>);
+
+impl TileData {
+    fn push(&mut self, tiles: Result<Tiles, TmxError>) -> Value {
+        self.0.push(tiles);
+        Value::Array(vec![Value::from(self.0.len() - 1)])
+    }
+}
+
+/// Base64 data stays compressed until the layer is read, as the compression may come after it.
+enum Tiles {
+    Gids(Vec<Gid>),
+    Bytes(Vec<u8>),
+}
+
+/// Where in the document the parsed value is, only the layers, chunks and their data need to be told apart.
+#[derive(Clone, Copy, PartialEq)]
+enum Level {
+    Map,
+    Layers,
+    Layer,
+    Chunks,
+    Chunk,
+    Data,
+    Other,
+}
+
+/// Builds the `Value` of the document like `serde_json` does, except for the tile data.
+struct Seed<'a> {
+    level: Level,
+    tile_data: &'a mut TileData,
+}
+
+impl Seed<'_> {
+    fn at(&mut self, level: Level) -> Seed<'_> {
+        Seed { level, tile_data: &mut *self.tile_data }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        Ok(match self.level {
+            Level::Data => self.tile_data.push(encoding::decode_base64_bytes(value).map(Tiles::Bytes)),
+            _ => Value::String(value.to_string()),
+        })
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut access: A) -> Result<Value, A::Error> {
+        if self.level == Level::Data {
+            return read_gids(access, self.tile_data);
+        }
+
+        let element = match self.level {
+            Level::Layers => Level::Layer,
+            Level::Chunks => Level::Chunk,
+            _ => Level::Other,
+        };
+        let mut values = Vec::new();
+        while let Some(value) = access.next_element_seed(self.at(element))? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut access: A) -> Result<Value, A::Error> {
+        let mut object = serde_json::Map::new();
+        while let Some(key) = access.next_key::<String>()? {
+            let level = match (self.level, key.as_str()) {
+                (Level::Map, "layers") | (Level::Layer, "layers") => Level::Layers,
+                (Level::Layer, "chunks") => Level::Chunks,
+                (Level::Layer, "data") | (Level::Chunk, "data") => Level::Data,
+                _ => Level::Other,
+            };
+            let value = access.next_value_seed(self.at(level))?;
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Reads an array of gids, an invalid one is reported once the layer is read, like any other invalid tile data.
+fn read_gids<'de, A: SeqAccess<'de>>(mut access: A, tile_data: &mut TileData) -> Result<Value, A::Error> {
+    let mut gids = Vec::new();
+    let mut invalid = None;
+    while let Some(gid) = access.next_element::<Value>()? {
+        match gid.as_u64().and_then(|gid| u32::try_from(gid).ok()) {
+            Some(gid) => gids.push(Gid(gid)),
+            None => {
+                invalid = Some(TmxError::InvalidTileData(format!("Invalid gid {}", gid)));
+                while access.next_element::<IgnoredAny>()?.is_some() {}
+            },
+        }
+    }
+
+    Ok(tile_data.push(invalid.map_or(Ok(Tiles::Gids(gids)), Err)))
+}
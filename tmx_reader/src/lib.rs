@@ -1,15 +1,46 @@
 use quick_xml::Reader;
 use quick_xml::events::Event;
 use std::str;
+use std::str::FromStr;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use quick_xml::events::BytesStart;
 
-//https://doc.mapeditor.org/en/stable/reference/tmx-map-format/
+mod animation;
+mod chunks;
+mod collision;
+mod coordinates;
+mod encoding;
+mod error;
+mod gid;
+#[cfg(feature = "json")]
+mod json;
+mod loader;
+mod project;
+mod properties;
+mod render;
+mod wang;
+#[cfg(feature = "world")]
+mod world;
+mod writer;
+
+pub use animation::{AnimationClock, Frame};
+pub use chunks::{Chunk, Chunks};
+pub use collision::{Collider, ColliderSource, WorldShape};
+pub use error::TmxError;
+pub use gid::{Affine, FlipFlags, Gid};
+pub use loader::{FileSystemLoader, ResourceLoader};
+pub use project::{ClassType, CustomType, EnumStorage, EnumType, Project};
+pub use properties::{Properties, Property, PropertyValue};
+pub use wang::{TerrainType, WangColor, WangId, WangSet, WangSetType, WangTile};
+#[cfg(feature = "world")]
+pub use world::{World, WorldMap, WorldPattern};
+use properties::merge_members;
+use loader::{NoLoader, RelativeLoader};
 
-const FLIPPED_HORIZONTALLY_FLAG:i64 = 0x80000000;
-const FLIPPED_VERTICALLY_FLAG:i64 = 0x40000000;
-const FLIPPED_DIAGONALLY_FLAG:i64 = 0x20000000;
-const CLEAR_MASK:i64 = !(FLIPPED_VERTICALLY_FLAG | FLIPPED_HORIZONTALLY_FLAG | FLIPPED_DIAGONALLY_FLAG);
+//https://doc.mapeditor.org/en/stable/reference/tmx-map-format/
 
 #[derive(PartialEq)]
 #[derive(Debug)]
@@ -51,7 +82,11 @@ pub enum PropertyType {
     Float,
     Bool,
     Color,
-    File
+    File,
+    Object,
+    Class,
+    /// Only used for resolved enum values, in the file enums are stored as strings or ints.
+    Enum
 }
 
 #[derive(PartialEq)]
@@ -61,36 +96,302 @@ pub enum DrawOrder {
     TopDown
 }
 
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+    Justify
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom
+}
+
+/// How the tiles of a layer are stored in its `<data>` element.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum DataEncoding {
+    /// One `<tile>` element per cell.
+    Xml,
+    Csv,
+    Base64
+}
+
+/// The compression of base64 encoded tile data.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Compression {
+    Zlib,
+    Gzip,
+    Zstd
+}
+
+/// The point of a tile object that is placed at the object's position.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum ObjectAlignment {
+    /// Bottom left for orthogonal maps, bottom for isometric ones.
+    Unspecified,
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum TileRenderSize {
+    /// Tiles are drawn in the size of the tileset.
+    Tile,
+    /// Tiles are drawn in the size of the map's grid.
+    Grid
+}
+
+/// How tiles are scaled when `TileRenderSize::Grid` is used.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum FillMode {
+    Stretch,
+    PreserveAspectFit
+}
+
+/// The formats Tiled saves maps, tilesets and templates in.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// `.tmx`, `.tsx` and `.tx` files.
+    Xml,
+    /// `.tmj`, `.tsj` and `.tj` files, only readable with the `json` feature.
+    Json,
+}
+
+impl Format {
+    /// Tells the formats apart by the first character of the document, JSON documents are objects.
+    pub fn detect(content: &str) -> Format {
+        match content.trim_start_matches('\u{feff}').trim_start().chars().next() {
+            Some('{') => Format::Json,
+            _ => Format::Xml,
+        }
+    }
+}
+
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Tileset {
     pub firstgid: i64,
+    /// The path of the `.tsx` file for external tilesets.
     pub source: Option<String>,
     pub spacing: i32,
     pub margin: i32,
     pub name: String,
+    pub class: Option<String>,
     pub tilewidth: i32,
     pub tileheight: i32,
     pub tilecount: i32,
+    /// 0 for image collection tilesets.
     pub columns: i32,
+    pub objectalignment: ObjectAlignment,
+    pub tilerendersize: TileRenderSize,
+    pub fillmode: FillMode,
+    /// The image all tiles are cut from, `None` for image collection tilesets.
+    pub image: Option<Image>,
     pub tiles: Vec<Tile>,
     pub tileoffset: Option<TileOffset>,
+    pub grid: Option<Grid>,
+    pub transformations: Option<Transformations>,
+    pub wangsets: Vec<WangSet>,
+    /// Only found in tilesets written before Tiled 1.5.
+    pub terraintypes: Vec<TerrainType>,
+    pub properties: Properties,
+}
+
+impl Tileset {
+    /// Returns the tile data for the given local tile id, if the tileset declares any.
+    pub fn tile(&self, id: i64) -> Option<&Tile> {
+        self.tiles.iter().find(|tile| tile.id == id)
+    }
+
+    /// Returns the image showing the tile with the given local id, the tileset image for atlases.
+    pub fn tile_image(&self, id: i64) -> Option<&Image> {
+        match self.image {
+            Some(ref image) => Some(image),
+            None => self.tile(id)?.image.as_ref(),
+        }
+    }
+
+    /// Returns the part of `tile_image(id)` that shows the tile, in pixels.
+    ///
+    /// For atlases the position follows from `columns`, `margin` and `spacing`,
+    /// tiles of image collections use their own sub-rectangle or the whole image.
+    /// `None` if the tileset has no such tile.
+    pub fn source_rect(&self, id: i64) -> Option<Rect> {
+        if self.image.is_some() {
+            if id < 0 || id >= i64::from(self.tilecount) || self.columns <= 0 {
+                return None;
+            }
+            let column = (id % i64::from(self.columns)) as i32;
+            let row = (id / i64::from(self.columns)) as i32;
+
+            return Some(Rect {
+                x: self.margin + column * (self.tilewidth + self.spacing),
+                y: self.margin + row * (self.tileheight + self.spacing),
+                width: self.tilewidth,
+                height: self.tileheight,
+            });
+        }
+
+        let tile = self.tile(id)?;
+        let image = tile.image.as_ref()?;
+
+        Some(Rect {
+            x: tile.x,
+            y: tile.y,
+            width: tile.width.unwrap_or(image.width - tile.x),
+            height: tile.height.unwrap_or(image.height.unwrap_or(self.tileheight) - tile.y),
+        })
+    }
+}
+
+/// A rectangle in pixels or tiles, `x` and `y` are the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// Whether the rectangles share some of their area, the far edges are summed as `i64` so they can't overflow.
+    pub(crate) fn overlaps(&self, other: Rect) -> bool {
+        let (x, y, right, bottom) = self.edges();
+        let (other_x, other_y, other_right, other_bottom) = other.edges();
+        x < other_right && other_x < right && y < other_bottom && other_y < bottom
+    }
+
+    fn edges(&self) -> (i64, i64, i64, i64) {
+        let (x, y) = (i64::from(self.x), i64::from(self.y));
+        (x, y, x + i64::from(self.width), y + i64::from(self.height))
+    }
+}
+
+/// A global tile id resolved to the tileset owning it.
+#[derive(Debug)]
+pub struct TileRef<'a> {
+    pub tileset: &'a Tileset,
+    /// The id of the tile local to its tileset.
+    pub id: i64,
+    pub tile: Option<&'a Tile>,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Tile {
     pub id: i64,
+    /// The `class` of the tile, older versions of Tiled wrote it as `type`.
     pub tile_type: Option<String>,
-    pub probability: u32,
-    pub terrain: Option<String>,
+    pub probability: f32,
+    /// The index into `Tileset::terraintypes` for the top left, top right, bottom left and bottom right corner.
+    pub terrain: Option<[Option<usize>; 4]>,
     pub image: Option<Image>,
+    /// The part of `image` used by the tile, `width` and `height` default to the size of the image.
+    pub x: i32,
+    pub y: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub properties: Properties,
+    pub animation: Vec<Frame>,
+    /// The collision shapes drawn on the tile, relative to its top left corner.
+    pub collision: Option<Objectgroup>,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct TileOffset {
     pub x: i64,
     pub y: i64,
 }
 
+/// The grid used for tile objects and the terrain overlay, only needed for isometric tiles.
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Grid {
+    pub orientation: Orientation,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The transformations allowed when placing tiles of the tileset, e.g. for Wang fills.
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Transformations {
+    pub hflip: bool,
+    pub vflip: bool,
+    pub rotate: bool,
+    pub preferuntransformed: bool,
+}
+
+/// A colour as written by Tiled, either `#RRGGBB` or `#AARRGGBB`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    /// Parses `#RRGGBB` or `#AARRGGBB`, the leading `#` is optional.
+    pub fn from_hex(hex: &str) -> Option<Rgba> {
+        let hex = hex.trim_start_matches('#');
+        if !hex.is_ascii() {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+        match hex.len() {
+            6 => Some(Rgba { r: component(0)?, g: component(2)?, b: component(4)?, a: 255 }),
+            8 => Some(Rgba { a: component(0)?, r: component(2)?, g: component(4)?, b: component(6)? }),
+            _ => None,
+        }
+    }
+
+    /// Formats the colour the way Tiled does, `#RRGGBB` if it is opaque and `#AARRGGBB` otherwise.
+    pub fn to_hex(&self) -> String {
+        if self.a == 255 {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.a, self.r, self.g, self.b)
+        }
+    }
+
+    /// Multiplies both colours component wise, the way Tiled combines tint colours.
+    pub fn multiply(self, other: Rgba) -> Rgba {
+        let mul = |a: u8, b: u8| ((u16::from(a) * u16::from(b) + 127) / 255) as u8;
+        Rgba { r: mul(self.r, other.r), g: mul(self.g, other.g), b: mul(self.b, other.b), a: mul(self.a, other.a) }
+    }
+}
+
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Layer {
     pub id: i64,
@@ -103,15 +404,65 @@ pub struct Layer {
     pub visible: bool,
     pub offsetx: i32,
     pub offsety: i32,
+    pub tintcolor: Option<Rgba>,
+    pub properties: Properties,
+    /// Global tile ids in row-major order, including the flip flags. Empty in infinite maps.
+    pub data: Vec<Gid>,
+    /// The tile data of infinite maps, empty otherwise.
+    pub chunks: Chunks,
+    pub infinite: bool,
+    /// How the tile data is stored in the file, `Map::write_to` uses it again.
+    pub encoding: DataEncoding,
+    /// Only used with `DataEncoding::Base64`.
+    pub compression: Option<Compression>,
 }
 
-#[derive(Debug)]
-pub struct Property {
-    pub name: String,
-    pub prop_type: PropertyType,
-    pub value: String,
+impl Layer {
+    /// Returns the global tile id at the given cell with the flip flags cleared.
+    ///
+    /// Empty cells yield `Some(0)`, cells outside of the layer `None`.
+    /// Infinite layers cover every cell, negative ones included.
+    pub fn gid_at(&self, x: i32, y: i32) -> Option<i64> {
+        self.raw_gid_at(x, y).map(Gid::id)
+    }
+
+    /// Like `gid_at`, but keeps the flip flags.
+    pub fn raw_gid_at(&self, x: i32, y: i32) -> Option<Gid> {
+        if self.infinite {
+            return Some(self.chunks.raw_gid_at(x, y).unwrap_or(Gid::EMPTY));
+        }
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        self.data.get((y * self.width + x) as usize).copied()
+    }
+
+    /// The cells holding tile data, in tiles. For infinite layers the area covered by chunks.
+    pub fn bounds(&self) -> Rect {
+        if self.infinite {
+            self.chunks.bounds().unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+        } else {
+            Rect { x: 0, y: 0, width: self.width, height: self.height }
+        }
+    }
+
+    /// Returns every non-empty cell as `(x, y, gid)`, the gid including the flip flags.
+    /// Infinite layers yield the cells chunk by chunk.
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32, Gid)> + '_ {
+        let width = self.width;
+        let cells = self.data.iter()
+            .enumerate()
+            .map(move |(i, gid)| (i as i32 % width, i as i32 / width, *gid));
+        let chunk_cells = self.chunks.iter()
+            .flat_map(|chunk| chunk.data.iter()
+                .enumerate()
+                .map(move |(i, gid)| (chunk.x + i as i32 % chunk.width, chunk.y + i as i32 / chunk.width, *gid)));
+
+        cells.chain(chunk_cells).filter(|(_, _, gid)| !gid.is_empty())
+    }
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Objectgroup {
     pub id: i64,
@@ -123,11 +474,13 @@ pub struct Objectgroup {
     pub visible: bool,
     pub offsetx: i32,
     pub offsety: i32,
+    pub tintcolor: Option<Rgba>,
     pub draworder: DrawOrder,
     pub objects: Vec<Object>,
-    pub properties: Option<Vec<Property>>,
+    pub properties: Properties,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Object {
     pub id: i64,
@@ -138,15 +491,46 @@ pub struct Object {
     pub width: f32,
     pub height: f32,
     pub rotation: f32,
-    pub gid: Option<i64>,
+    /// Set for tile objects, including the flip flags.
+    pub gid: Option<Gid>,
     pub visible: bool,
     pub template: Option<String>,
-    pub properties: Option<Vec<Property>>,
-    pub flipped_horizontally: bool,
-    pub flipped_vertically: bool,
-    pub flipped_diagonally: bool,
+    pub properties: Properties,
+    pub shape: ObjectShape,
+}
+
+/// The shape of an object, points of polygons and polylines are relative to the object's position.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum ObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<(f32, f32)>),
+    Polyline(Vec<(f32, f32)>),
+    Text(Text),
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Text {
+    pub fontfamily: String,
+    pub pixelsize: i32,
+    pub wrap: bool,
+    pub color: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikeout: bool,
+    pub kerning: bool,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+    pub text: String,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Imagelayer {
     pub id: i64,
@@ -157,9 +541,20 @@ pub struct Imagelayer {
     pub y: f32,
     pub opacity: f32,
     pub visible: bool,
-
+    pub tintcolor: Option<Rgba>,
+    /// Whether the image is repeated along the x axis.
+    pub repeatx: bool,
+    /// Whether the image is repeated along the y axis.
+    pub repeaty: bool,
+    /// Horizontal scrolling speed relative to the camera, 1.0 moves along with the tiles.
+    pub parallaxx: f32,
+    /// Vertical scrolling speed relative to the camera, 1.0 moves along with the tiles.
+    pub parallaxy: f32,
+    pub image: Option<Image>,
+    pub properties: Properties,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Image {
     pub format: Option<String>,
@@ -169,6 +564,7 @@ pub struct Image {
     pub height: Option<i32>,
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Group {
     pub id: i64,
@@ -177,8 +573,141 @@ pub struct Group {
     pub offsety: i32,
     pub opacity: f32,
     pub visible: bool,
+    pub tintcolor: Option<Rgba>,
+    pub properties: Properties,
+    /// The layers inside of this group, in document order.
+    pub layers: Vec<LayerKind>,
+}
+
+impl Group {
+    /// Returns every layer below this group, depth first in document order, with its effective values.
+    pub fn effective_layers(&self) -> Vec<(&LayerKind, EffectiveValues)> {
+        let values = EffectiveValues::default()
+            .combine_values(self.offsetx, self.offsety, self.opacity, self.visible, self.tintcolor);
+        let mut result = Vec::new();
+        collect_effective_layers(&self.layers, values, &mut result);
+        result
+    }
+}
+
+fn collect_effective_layers<'a>(layers: &'a [LayerKind], parent: EffectiveValues, result: &mut Vec<(&'a LayerKind, EffectiveValues)>) {
+    for layer in layers {
+        let values = parent.combine(layer);
+        result.push((layer, values));
+        if let LayerKind::Group(group) = layer {
+            collect_effective_layers(&group.layers, values, result);
+        }
+    }
+}
+
+/// A layer of a map together with its place in the draw order and its effective values.
+#[derive(Debug)]
+pub struct MapLayer<'a, T> {
+    /// Position of the layer in document order, counting every layer including groups.
+    /// Layers with a higher z are drawn on top of the ones with a lower z.
+    pub z: usize,
+    pub layer: &'a T,
+    pub values: EffectiveValues,
+}
+
+// Derived Clone and Copy would require T to be Clone and Copy as well.
+impl<'a, T> Clone for MapLayer<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for MapLayer<'a, T> {}
+
+/// A layer of any kind, as it appears in a map or a group.
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum LayerKind {
+    Tile(Layer),
+    Object(Objectgroup),
+    Image(Imagelayer),
+    Group(Group),
+}
+
+macro_rules! layer_field {
+    ($layer:expr, $field:ident) => {
+        match $layer {
+            LayerKind::Tile(layer) => &layer.$field,
+            LayerKind::Object(layer) => &layer.$field,
+            LayerKind::Image(layer) => &layer.$field,
+            LayerKind::Group(layer) => &layer.$field,
+        }
+    };
+}
+
+impl LayerKind {
+    pub fn id(&self) -> i64 {
+        *layer_field!(self, id)
+    }
+
+    pub fn name(&self) -> &str {
+        layer_field!(self, name)
+    }
+
+    pub fn offsetx(&self) -> i32 {
+        *layer_field!(self, offsetx)
+    }
+
+    pub fn offsety(&self) -> i32 {
+        *layer_field!(self, offsety)
+    }
+
+    pub fn opacity(&self) -> f32 {
+        *layer_field!(self, opacity)
+    }
+
+    pub fn visible(&self) -> bool {
+        *layer_field!(self, visible)
+    }
+
+    pub fn tintcolor(&self) -> Option<Rgba> {
+        *layer_field!(self, tintcolor)
+    }
+}
+
+/// The values a layer is rendered with once everything inherited from its parent groups is applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EffectiveValues {
+    pub offsetx: i32,
+    pub offsety: i32,
+    pub opacity: f32,
+    pub visible: bool,
+    pub tintcolor: Option<Rgba>,
+}
+
+impl Default for EffectiveValues {
+    fn default() -> EffectiveValues {
+        EffectiveValues { offsetx: 0, offsety: 0, opacity: 1.0, visible: true, tintcolor: None }
+    }
+}
+
+impl EffectiveValues {
+    /// Applies a child layer to these values: offsets add up, opacities and tints multiply and a
+    /// layer is only visible if all of its parents are.
+    pub fn combine(&self, layer: &LayerKind) -> EffectiveValues {
+        self.combine_values(layer.offsetx(), layer.offsety(), layer.opacity(), layer.visible(), layer.tintcolor())
+    }
+
+    fn combine_values(&self, offsetx: i32, offsety: i32, opacity: f32, visible: bool, tintcolor: Option<Rgba>) -> EffectiveValues {
+        EffectiveValues {
+            offsetx: self.offsetx + offsetx,
+            offsety: self.offsety + offsety,
+            opacity: self.opacity * opacity,
+            visible: self.visible && visible,
+            tintcolor: match (self.tintcolor, tintcolor) {
+                (Some(parent), Some(child)) => Some(parent.multiply(child)),
+                (parent, child) => parent.or(child),
+            },
+        }
+    }
 }
 
+#[derive(PartialEq)]
 #[derive(Debug)]
 pub struct Map {
     pub version: String,
@@ -188,172 +717,538 @@ pub struct Map {
     pub height: i32,
     pub tilewidth: i32,
     pub tileheight: i32,
-    pub backgroundcolor: String,
+    /// `None` if the map has no background color of its own.
+    pub backgroundcolor: Option<String>,
     pub nextobjectid: i32,
     pub staggeraxis: StaggerAxis,
     pub staggerindex: StaggerIndex,
-    pub tileset: Tileset,
-    pub objectgroups: Vec<Objectgroup>,
-    pub properties: Option<Vec<Property>>
-    //infinite ??? desert.tmx
+    /// The length of the straight sides of hexagonal tiles, along the stagger axis.
+    pub hexsidelength: i32,
+    pub tilesets: Vec<Tileset>,
+    /// The top level layers in document order, groups hold their children themselves.
+    pub layers: Vec<LayerKind>,
+    pub properties: Properties,
+    /// Tile layers of infinite maps store their tiles in chunks.
+    pub infinite: bool,
 }
 
 impl Map {
-    pub fn new(xml: &str) -> Map {
-        let mut reader = Reader::from_str(xml);
+    /// Resolves a global tile id, flip flags are ignored.
+    ///
+    /// Returns `None` for empty cells and ids not covered by any tileset. The ids of image collections can have gaps,
+    /// only the tiles the collection lists are covered by it.
+    pub fn tile_for_gid(&self, gid: i64) -> Option<TileRef<'_>> {
+        let gid = gid & !i64::from(Gid::FLAGS);
+        if gid == 0 {
+            return None;
+        }
+
+        let tileset = self.tilesets.iter()
+            .filter(|tileset| tileset.firstgid <= gid)
+            .max_by_key(|tileset| tileset.firstgid)?;
+        let id = gid - tileset.firstgid;
+
+        let covered = match tileset.image {
+            Some(_) => id < i64::from(tileset.tilecount),
+            None => tileset.tile(id).is_some(),
+        };
+        if !covered {
+            return None;
+        }
+
+        Some(TileRef {
+            tileset,
+            id,
+            tile: tileset.tile(id),
+        })
+    }
+
+    /// Returns every layer of the map, including the ones nested in groups, in document order.
+    pub fn layers(&self) -> impl Iterator<Item = MapLayer<'_, LayerKind>> {
+        let mut layers = Vec::new();
+        collect_effective_layers(&self.layers, EffectiveValues::default(), &mut layers);
+
+        layers.into_iter()
+            .enumerate()
+            .map(|(z, (layer, values))| MapLayer { z, layer, values })
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = MapLayer<'_, Layer>> {
+        self.layers().filter_map(|l| match l.layer {
+            LayerKind::Tile(layer) => Some(MapLayer { z: l.z, layer, values: l.values }),
+            _ => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = MapLayer<'_, Objectgroup>> {
+        self.layers().filter_map(|l| match l.layer {
+            LayerKind::Object(layer) => Some(MapLayer { z: l.z, layer, values: l.values }),
+            _ => None,
+        })
+    }
+
+    pub fn image_layers(&self) -> impl Iterator<Item = MapLayer<'_, Imagelayer>> {
+        self.layers().filter_map(|l| match l.layer {
+            LayerKind::Image(layer) => Some(MapLayer { z: l.z, layer, values: l.values }),
+            _ => None,
+        })
+    }
+
+    pub fn group_layers(&self) -> impl Iterator<Item = MapLayer<'_, Group>> {
+        self.layers().filter_map(|l| match l.layer {
+            LayerKind::Group(layer) => Some(MapLayer { z: l.z, layer, values: l.values }),
+            _ => None,
+        })
+    }
+
+    /// Fills in the defaults of class properties and resolves enum properties using the types defined in `project`.
+    pub fn apply_project(&mut self, project: &Project) -> Result<(), TmxError> {
+        project.resolve(&mut self.properties)?;
+        for tileset in &mut self.tilesets {
+            project.resolve(&mut tileset.properties)?;
+            for tile in &mut tileset.tiles {
+                project.resolve(&mut tile.properties)?;
+            }
+            for wangset in &mut tileset.wangsets {
+                project.resolve(&mut wangset.properties)?;
+                for color in &mut wangset.colors {
+                    project.resolve(&mut color.properties)?;
+                }
+            }
+            for terraintype in &mut tileset.terraintypes {
+                project.resolve(&mut terraintype.properties)?;
+            }
+        }
+        apply_project_to_layers(&mut self.layers, project)
+    }
+
+    /// Parses a map, external tilesets and templates can't be resolved and result in a `TmxError::Resource`.
+    pub fn parse(xml: &str) -> Result<Map, TmxError> {
+        Map::parse_with_loader(xml, &NoLoader)
+    }
+
+    /// Loads the map file at `path` in either format, external tilesets and templates are loaded relative to it.
+    /// Both formats are parsed while they are read, the tile data of JSON files is decoded as it comes along.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Map, TmxError> {
+        let path = path.as_ref();
+        let resource_error = |error| TmxError::Resource { path: path.to_string_lossy().to_string(), error };
+
+        let mut file = BufReader::new(File::open(path).map_err(resource_error)?);
+        let format = Format::detect(&String::from_utf8_lossy(file.fill_buf().map_err(resource_error)?));
+        let loader = FileSystemLoader::new(path.parent().unwrap_or_else(|| Path::new("")));
+
+        match format {
+            Format::Xml => Map::from_reader_with_loader(file, &loader),
+            #[cfg(feature = "json")]
+            Format::Json => {
+                if file.fill_buf().map_err(resource_error)?.starts_with("\u{feff}".as_bytes()) {
+                    file.consume("\u{feff}".len());
+                }
+                Map::from_json_reader_with_loader(file, &loader)
+            },
+            #[cfg(not(feature = "json"))]
+            Format::Json => Err(json_disabled()),
+        }
+    }
+
+    /// Parses a map in either format, see `Format::detect`.
+    pub fn parse_any(content: &str) -> Result<Map, TmxError> {
+        Map::parse_any_with_loader(content, &NoLoader)
+    }
+
+    /// Parses a map in either format, external tilesets and templates may use either format as well.
+    pub fn parse_any_with_loader(content: &str, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        match Format::detect(content) {
+            Format::Xml => Map::parse_with_loader(content, loader),
+            #[cfg(feature = "json")]
+            Format::Json => Map::parse_json_with_loader(content, loader),
+            #[cfg(not(feature = "json"))]
+            Format::Json => Err(json_disabled()),
+        }
+    }
+
+    /// Parses a map whose external tilesets and templates are loaded through the given loader.
+    pub fn parse_with_loader(xml: &str, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        Map::from_reader_with_loader(xml.as_bytes(), loader)
+    }
+
+    /// Parses a TMX map while reading it, e.g. from a network stream or a file inside an archive.
+    /// The document is never held in memory as a whole, external tilesets and templates can't be resolved.
+    pub fn from_reader<R: BufRead>(source: R) -> Result<Map, TmxError> {
+        Map::from_reader_with_loader(source, &NoLoader)
+    }
+
+    /// Like `from_reader`, external tilesets and templates are loaded through the given loader.
+    pub fn from_reader_with_loader<R: BufRead>(source: R, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        let mut reader = Reader::from_reader(source);
         reader.trim_text(true);
 
         let mut buf = Vec::new();
 
-        let mut map: Option<Map> = None;
-
         loop {
             match reader.read_event(&mut buf) {
-                Ok(Event::Start(ref e)) => {
-                    match e.name() {
-                        b"map" => {
-                            map = Some(read_map(e, &mut reader));
-                        },
-                        _ => (),
-                    }
-                },
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
+                Ok(Event::Start(ref e)) if e.name() == b"map" => return read_map(e, &mut reader, loader),
+                Err(e) => return Err(xml_error(&reader, e)),
+                Ok(Event::Eof) => return Err(TmxError::MissingElement("map".to_string())),
                 _ => (),
             }
             buf.clear();
         }
-
-        //TODO this can be better
-        map.unwrap()
     }
 }
 
-fn propertytype_from_string(propertytype: &String) -> PropertyType {
+fn propertytype_from_string(propertytype: &str) -> Option<PropertyType> {
     match propertytype.to_lowercase().as_ref() {
-        "string" => PropertyType::String,
-        "int" => PropertyType::Int,
-        "float" => PropertyType::Float,
-        "bool" => PropertyType::Bool,
-        "color" => PropertyType::Color,
-        "file" => PropertyType::File,
-        _ => panic!("Unsupported propertytype: {}", propertytype),
+        "string" => Some(PropertyType::String),
+        "int" => Some(PropertyType::Int),
+        "float" => Some(PropertyType::Float),
+        "bool" => Some(PropertyType::Bool),
+        "color" => Some(PropertyType::Color),
+        "file" => Some(PropertyType::File),
+        "object" => Some(PropertyType::Object),
+        "class" => Some(PropertyType::Class),
+        _ => None,
     }
 }
 
-fn staggerindex_from_string(staggerindex: &String) -> StaggerIndex {
+fn staggerindex_from_string(staggerindex: &str) -> Option<StaggerIndex> {
     match staggerindex.to_lowercase().as_ref() {
-        "even" => StaggerIndex::Even,
-        "odd" => StaggerIndex::Odd,
-        _ => panic!("Unsupported staggerindex: {}", staggerindex),
+        "even" => Some(StaggerIndex::Even),
+        "odd" => Some(StaggerIndex::Odd),
+        _ => None,
     }
 }
 
-fn staggeraxis_from_string(staggeraxis: &String) -> StaggerAxis {
+fn staggeraxis_from_string(staggeraxis: &str) -> Option<StaggerAxis> {
     match staggeraxis.to_lowercase().as_ref() {
-        "x" => StaggerAxis::X,
-        "y" => StaggerAxis::Y,
-        _ => panic!("Unsupported staggeraxis: {}", staggeraxis),
+        "x" => Some(StaggerAxis::X),
+        "y" => Some(StaggerAxis::Y),
+        _ => None,
     }
 }
 
-fn renderorder_from_string(renderorder: &String) -> Renderorder {
+fn renderorder_from_string(renderorder: &str) -> Option<Renderorder> {
     match renderorder.to_lowercase().as_ref() {
-        "right-down" => Renderorder::RightDown,
-        "right-up" => Renderorder::RightUp,
-        "left-down" => Renderorder::LeftDown,
-        "left-up" => Renderorder::LeftUp,
-        _ => panic!("Unsupported renderorder: {}", renderorder),
+        "right-down" => Some(Renderorder::RightDown),
+        "right-up" => Some(Renderorder::RightUp),
+        "left-down" => Some(Renderorder::LeftDown),
+        "left-up" => Some(Renderorder::LeftUp),
+        _ => None,
     }
 }
 
-fn orientation_from_string(orientation: &String) -> Orientation {
+fn orientation_from_string(orientation: &str) -> Option<Orientation> {
     match orientation.to_lowercase().as_ref() {
-        "orthogonal" => Orientation::Orthogonal,
-        "isometric" => Orientation::Isometric,
-        "staggered" => Orientation::Staggered,
-        "hexagonal" => Orientation::Hexagonal,
-        _ => panic!("Unsupported orientation: {}", orientation),
+        "orthogonal" => Some(Orientation::Orthogonal),
+        "isometric" => Some(Orientation::Isometric),
+        "staggered" => Some(Orientation::Staggered),
+        "hexagonal" => Some(Orientation::Hexagonal),
+        _ => None,
     }
 }
 
-fn draworder_from_string(draworder: &String) -> DrawOrder {
-    match draworder.to_lowercase().as_ref() {
-        "index" => DrawOrder::Index,
-        "topdown" => DrawOrder::TopDown,
-        _ => panic!("Unsupported draworder: {}", draworder),
+fn objectalignment_from_string(objectalignment: &str) -> Option<ObjectAlignment> {
+    match objectalignment {
+        "unspecified" => Some(ObjectAlignment::Unspecified),
+        "topleft" => Some(ObjectAlignment::TopLeft),
+        "top" => Some(ObjectAlignment::Top),
+        "topright" => Some(ObjectAlignment::TopRight),
+        "left" => Some(ObjectAlignment::Left),
+        "center" => Some(ObjectAlignment::Center),
+        "right" => Some(ObjectAlignment::Right),
+        "bottomleft" => Some(ObjectAlignment::BottomLeft),
+        "bottom" => Some(ObjectAlignment::Bottom),
+        "bottomright" => Some(ObjectAlignment::BottomRight),
+        _ => None,
     }
 }
 
-fn read_property(e: &BytesStart) -> Property {
-
-    let kv = extract_attributes(e);
-
-    Property {
-        name: kv.get("name").map(|s| s.to_string()).unwrap(),
-        value: kv.get("value").map(|s| s.to_string()).unwrap(),
-        prop_type: kv.get("type").map(|s| propertytype_from_string(s)).unwrap_or(PropertyType::String),
+fn tilerendersize_from_string(tilerendersize: &str) -> Option<TileRenderSize> {
+    match tilerendersize {
+        "tile" => Some(TileRenderSize::Tile),
+        "grid" => Some(TileRenderSize::Grid),
+        _ => None,
     }
 }
 
-fn read_tileoffset(e: &BytesStart) -> TileOffset {
-
-    let kv = extract_attributes(e);
+fn fillmode_from_string(fillmode: &str) -> Option<FillMode> {
+    match fillmode {
+        "stretch" => Some(FillMode::Stretch),
+        "preserve-aspect-fit" => Some(FillMode::PreserveAspectFit),
+        _ => None,
+    }
+}
 
-    TileOffset {
-        x: kv.get("x").unwrap().parse::<i64>().unwrap(),
-        y: kv.get("y").unwrap().parse::<i64>().unwrap(),
+fn wangsettype_from_string(wangsettype: &str) -> Option<WangSetType> {
+    match wangsettype {
+        "corner" => Some(WangSetType::Corner),
+        "edge" => Some(WangSetType::Edge),
+        "mixed" => Some(WangSetType::Mixed),
+        _ => None,
     }
 }
 
-fn read_properties(reader: &mut Reader<&[u8]>) -> Vec<Property> {
+/// Parses the eight colors of a Wang id, e.g. `0,1,0,2,0,2,0,1`.
+/// Before Tiled 1.5 they were written as a single number with four bits per color, e.g. `0x10202010`.
+fn wangid_from_string(wangid: &str) -> Option<WangId> {
+    let mut colors = [0u8; 8];
 
-    let mut properties: Vec<Property> = Vec::new();
-    let mut buf = Vec::new();
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.name() {
-                    _ => exhaust(reader),
-                }
-            },
-            Ok(Event::Empty(ref e)) => {
-                match e.name() {
-                    b"property" => properties.push(read_property(e)),
-                    _           => exhaust(reader),
-                }
-            },
-            Ok(Event::End(ref e)) => {match e.name() {
-                b"properties"   => break,
-                _               => (),
-            }},
-            Err(e)          => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            Ok(Event::Eof)  => break,
-            _               => (),
+    if let Some(hex) = wangid.strip_prefix("0x") {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = ((value >> (i * 4)) & 0xf) as u8;
         }
+        return Some(WangId(colors));
     }
-    buf.clear();
 
-    properties
+    let mut values = wangid.split(',');
+    for color in colors.iter_mut() {
+        *color = values.next()?.trim().parse().ok()?;
+    }
+    match values.next() {
+        None => Some(WangId(colors)),
+        Some(_) => None,
+    }
 }
 
-fn read_image(e: &BytesStart) -> Image {
-
-    let kv = extract_attributes(e);
+/// Parses the terrain of the four corners of a tile, e.g. `0,0,,1`. Empty corners have no terrain.
+fn terrain_from_string(terrain: &str) -> Option<[Option<usize>; 4]> {
+    let mut corners = [None; 4];
+    let mut values = terrain.split(',');
 
-    Image {
-        format: kv.get("format").map(|s| s.to_string()),
-        source: kv.get("source").unwrap().to_string(),
-        trans: kv.get("trans").map(|s| s.to_string()),
-        width: kv.get("width").unwrap().parse::<i32>().unwrap(),
-        height: kv.get("height").map(|s| s.parse::<i32>().unwrap()),
+    for corner in corners.iter_mut() {
+        *corner = match values.next()?.trim() {
+            "" => None,
+            value => Some(value.parse().ok()?),
+        };
+    }
+    match values.next() {
+        None => Some(corners),
+        Some(_) => None,
     }
 }
 
+fn encoding_from_string(encoding: &str) -> Option<DataEncoding> {
+    match encoding {
+        "csv" => Some(DataEncoding::Csv),
+        "base64" => Some(DataEncoding::Base64),
+        _ => None,
+    }
+}
 
-fn read_object(e: &BytesStart, reader: &mut Reader<&[u8]>, is_empty: bool) -> Object{
-
-    let mut properties: Option<Vec<Property>> = None;
+fn compression_from_string(compression: &str) -> Option<Compression> {
+    match compression {
+        "zlib" => Some(Compression::Zlib),
+        "gzip" => Some(Compression::Gzip),
+        "zstd" => Some(Compression::Zstd),
+        _ => None,
+    }
+}
+
+fn draworder_from_string(draworder: &str) -> Option<DrawOrder> {
+    match draworder.to_lowercase().as_ref() {
+        "index" => Some(DrawOrder::Index),
+        "topdown" => Some(DrawOrder::TopDown),
+        _ => None,
+    }
+}
+
+fn halign_from_string(halign: &str) -> Option<HorizontalAlignment> {
+    match halign.to_lowercase().as_ref() {
+        "left" => Some(HorizontalAlignment::Left),
+        "center" => Some(HorizontalAlignment::Center),
+        "right" => Some(HorizontalAlignment::Right),
+        "justify" => Some(HorizontalAlignment::Justify),
+        _ => None,
+    }
+}
+
+fn valign_from_string(valign: &str) -> Option<VerticalAlignment> {
+    match valign.to_lowercase().as_ref() {
+        "top" => Some(VerticalAlignment::Top),
+        "center" => Some(VerticalAlignment::Center),
+        "bottom" => Some(VerticalAlignment::Bottom),
+        _ => None,
+    }
+}
+
+fn bool_from_string(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_ref() {
+        "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Multiline string properties keep their value as the content of the element instead of an attribute,
+/// class properties hold their members in a nested `<properties>` element.
+fn read_property<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool) -> Result<Property, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+
+    let mut text = attrs.string("value").unwrap_or_default();
+    let mut members = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        // Multiline strings are the content of the element, their leading and trailing whitespace belongs to them.
+        reader.trim_text(false);
+        loop {
+            let event = reader.read_event(&mut buf);
+            reader.trim_text(true);
+            match event {
+                Ok(Event::Start(ref e)) if e.name() == b"properties" => members = read_properties(reader)?,
+                Ok(Event::Start(ref _e)) => exhaust(reader)?,
+                Ok(Event::Text(ref t)) if attrs.get("value").is_none() =>
+                    text.push_str(&t.unescape_and_decode(reader).map_err(|e| xml_error(reader, e))?),
+                Ok(Event::End(ref e)) if e.name() == b"property" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "property")),
+                Err(e)          => return Err(xml_error(reader, e)),
+                _               => (),
+            }
+            buf.clear();
+        }
+    }
+
+    property_from_attributes(&attrs, text, members)
+}
+
+/// `text` is the value of string, file and color properties, `members` the ones of class properties.
+fn property_from_attributes(attrs: &Attributes, text: String, members: Properties) -> Result<Property, TmxError> {
+
+    let value = match attrs.value("type", propertytype_from_string)?.unwrap_or(PropertyType::String) {
+        PropertyType::String => PropertyValue::String(text),
+        PropertyType::Int    => PropertyValue::Int(attrs.number_or("value", 0)?),
+        PropertyType::Float  => PropertyValue::Float(attrs.number_or("value", 0.0)?),
+        PropertyType::Bool   => PropertyValue::Bool(attrs.bool_or("value", false)?),
+        // Tiled writes an empty value for colours that were never set.
+        PropertyType::Color if text.is_empty() => PropertyValue::Color(Rgba { r: 0, g: 0, b: 0, a: 0 }),
+        PropertyType::Color  => PropertyValue::Color(attrs.required_value("value", Rgba::from_hex)?),
+        PropertyType::File   => PropertyValue::File(PathBuf::from(text)),
+        PropertyType::Object => PropertyValue::Object(attrs.number_or("value", 0)?),
+        PropertyType::Class  => PropertyValue::Class(members),
+        PropertyType::Enum   => return Err(attrs.unknown_value("type", "enum")),
+    };
+
+    Ok(Property {
+        name: attrs.required("name")?.to_string(),
+        propertytype: attrs.string("propertytype"),
+        value,
+    })
+}
+
+fn tileoffset_from_attributes(attrs: &Attributes) -> Result<TileOffset, TmxError> {
+
+    Ok(TileOffset {
+        x: attrs.required_number("x")?,
+        y: attrs.required_number("y")?,
+    })
+}
+
+fn read_properties<R: BufRead>(reader: &mut Reader<R>) -> Result<Properties, TmxError> {
+
+    let mut properties: Vec<Property> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"property" => properties.push(read_property(e, reader, false)?),
+            Ok(Event::Empty(ref e)) if e.name() == b"property" => properties.push(read_property(e, reader, true)?),
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == b"properties" => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "properties")),
+            _               => (),
+        }
+        buf.clear();
+    }
+
+    Ok(Properties::new(properties))
+}
+
+fn read_image<R: BufRead>(reader: &Reader<R>, e: &BytesStart) -> Result<Image, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+
+    Ok(Image {
+        format: attrs.string("format"),
+        source: attrs.required("source")?.to_string(),
+        trans: attrs.string("trans"),
+        width: attrs.required_number("width")?,
+        height: attrs.number("height")?,
+    })
+}
+
+
+/// Reads the `points` of a `<polygon>` or `<polyline>`, e.g. `0,0 32,-16 64,0`.
+fn read_points<R: BufRead>(reader: &Reader<R>, e: &BytesStart) -> Result<Vec<(f32, f32)>, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let points = attrs.required("points")?;
+
+    points.split_whitespace().map(|point| {
+        let mut coordinates = point.split(',').map(|c| c.parse::<f32>());
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+            _ => Err(TmxError::InvalidNumber {
+                element: attrs.element.clone(),
+                attribute: "points".to_string(),
+                value: point.to_string(),
+            }),
+        }
+    }).collect()
+}
+
+fn text_from_attributes(attrs: &Attributes, text: String) -> Result<Text, TmxError> {
+
+    Ok(Text {
+        fontfamily: attrs.get("fontfamily").unwrap_or("sans-serif").to_string(),
+        pixelsize: attrs.number_or("pixelsize", 16)?,
+        wrap: attrs.bool_or("wrap", false)?,
+        color: attrs.get("color").unwrap_or("#000000").to_string(),
+        bold: attrs.bool_or("bold", false)?,
+        italic: attrs.bool_or("italic", false)?,
+        underline: attrs.bool_or("underline", false)?,
+        strikeout: attrs.bool_or("strikeout", false)?,
+        kerning: attrs.bool_or("kerning", true)?,
+        halign: attrs.value("halign", halign_from_string)?.unwrap_or(HorizontalAlignment::Left),
+        valign: attrs.value("valign", valign_from_string)?.unwrap_or(VerticalAlignment::Top),
+        text,
+    })
+}
+
+/// Objects based on a template only carry the attributes, properties and shape that differ from it,
+/// everything else is taken from the template.
+fn read_object<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, context: &mut Context) -> Result<Object, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let (properties, shape) = read_object_content(reader, is_empty)?;
+
+    object_with_template(attrs, properties, shape, context)
+}
+
+/// Builds an object, filling in what isn't given from its template if it has one.
+fn object_with_template(attrs: Attributes, properties: Properties, shape: Option<ObjectShape>, context: &mut Context) -> Result<Object, TmxError> {
+    match attrs.get("template") {
+        None => object_from_attributes(&attrs, properties, shape),
+        Some(path) => {
+            let template = context.template(path)?;
+
+            let mut merged = template.attrs.clone();
+            merged.values.extend(attrs.values.clone());
+
+            object_from_attributes(
+                &merged,
+                merge_members(&template.properties, &properties),
+                shape.or_else(|| template.shape.clone()))
+        },
+    }
+}
+
+/// Reads the properties and the shape of an object, `None` as shape means it isn't given explicitly.
+fn read_object_content<R: BufRead>(reader: &mut Reader<R>, is_empty: bool) -> Result<(Properties, Option<ObjectShape>), TmxError> {
+
+    let mut properties = Properties::default();
+    let mut shape = None;
 
     if !is_empty {
         let mut buf = Vec::new();
@@ -361,366 +1256,2935 @@ fn read_object(e: &BytesStart, reader: &mut Reader<&[u8]>, is_empty: bool) -> Ob
             match reader.read_event(&mut buf) {
                 Ok(Event::Start(ref e)) => {
                     match e.name() {
-                        b"properties" => properties = Some(read_properties(reader)),
-                        _ => exhaust(reader),
+                        b"properties" => properties = read_properties(reader)?,
+                        b"text" => shape = Some(ObjectShape::Text(text_from_attributes(&extract_attributes(reader, e)?, read_text(reader, b"text")?)?)),
+                        _ => exhaust(reader)?,
                     }
                 },
                 Ok(Event::Empty(ref e)) => {
                     match e.name() {
-                        _ => exhaust(reader),
+                        b"ellipse"  => shape = Some(ObjectShape::Ellipse),
+                        b"point"    => shape = Some(ObjectShape::Point),
+                        b"polygon"  => shape = Some(ObjectShape::Polygon(read_points(reader, e)?)),
+                        b"polyline" => shape = Some(ObjectShape::Polyline(read_points(reader, e)?)),
+                        b"text"     => shape = Some(ObjectShape::Text(text_from_attributes(&extract_attributes(reader, e)?, String::new())?)),
+                        _           => (),
                     }
                 },
-                Ok(Event::End(ref e)) => {match e.name() {
-                    b"object" => break,
-                    _ => (),
-                }},
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
+                Ok(Event::End(ref e)) if e.name() == b"object" => break,
+                Err(e) => return Err(xml_error(reader, e)),
+                Ok(Event::Eof) => return Err(unexpected_eof(reader, "object")),
                 _ => (),
             }
+            buf.clear();
         }
     }
 
-    let kv = extract_attributes(e);
+    Ok((properties, shape))
+}
 
-    let gid = kv.get("gid").map(|gid| gid.to_string().parse::<i64>().unwrap());
-    Object {
-        x:kv.get("x").unwrap().parse::<f32>().unwrap(),
-        y:kv.get("y").unwrap().parse::<f32>().unwrap(),
-        width: kv.get("width").unwrap_or(&"0".to_string()).parse::<f32>().unwrap(),
-        height: kv.get("height").unwrap_or(&"0".to_string()).parse::<f32>().unwrap(),
-        id: kv.get("id").unwrap().parse::<i64>().unwrap(),
-        name: kv.get("name").map(|s| s.to_string()),
-        visible: kv.get("visible").unwrap_or(&"true".to_string()).parse::<bool>().unwrap(),
-        object_type: kv.get("type").map(|s| s.to_string()),
-        template: kv.get("template").map(|s| s.to_string()),
-        rotation: kv.get("rotation").unwrap_or(&"0".to_string()).parse::<f32>().unwrap(),
-        gid: gid.map(|gid| gid & CLEAR_MASK),
-        properties: properties,
-        flipped_horizontally: gid.map(|gid| gid & FLIPPED_HORIZONTALLY_FLAG > 0).unwrap_or(false),
-        flipped_diagonally: gid.map(|gid| gid & FLIPPED_DIAGONALLY_FLAG > 0).unwrap_or(false),
-        flipped_vertically: gid.map(|gid| gid & FLIPPED_VERTICALLY_FLAG > 0).unwrap_or(false),
-    }
+fn object_from_attributes(attrs: &Attributes, properties: Properties, shape: Option<ObjectShape>) -> Result<Object, TmxError> {
+
+    Ok(Object {
+        x: attrs.required_number("x")?,
+        y: attrs.required_number("y")?,
+        width: attrs.number_or("width", 0.0)?,
+        height: attrs.number_or("height", 0.0)?,
+        id: attrs.required_number("id")?,
+        name: attrs.string("name"),
+        visible: attrs.bool_or("visible", true)?,
+        object_type: attrs.string("type"),
+        template: attrs.string("template"),
+        rotation: attrs.number_or("rotation", 0.0)?,
+        gid: attrs.number("gid")?.map(Gid),
+        properties,
+        shape: shape.unwrap_or(ObjectShape::Rectangle),
+    })
 }
 
-fn read_objectgroup(e: &BytesStart, reader: &mut Reader<&[u8]>) -> Objectgroup{
+fn read_objectgroup<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, context: &mut Context) -> Result<Objectgroup, TmxError> {
 
+    let attrs = extract_attributes(reader, e)?;
     let mut objects: Vec<Object> = Vec::new();
-    let mut properties: Option<Vec<Property>> = None;
+    let mut properties = Properties::default();
 
-    let mut buf = Vec::new();
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Empty(ref e)) => {
-                match e.name() {
-                    b"object" => {
-                        objects.push(read_object(e, reader, true))
-                    },
-                    _ => exhaust(reader),
-                };
-            },
-            Ok(Event::Start(ref e)) => {
-                match e.name() {
-                    b"object"       => objects.push(read_object(e, reader, false)) ,
-                    b"properties"   => properties = Some(read_properties(reader)),
-                    _               => exhaust(reader),
-                }
-            },
-            Ok(Event::End(ref e)) => {match e.name() {
-                b"objectgroup"  => break,
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Empty(ref e)) if e.name() == b"object" => objects.push(read_object(e, reader, true, context)?),
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"object"       => objects.push(read_object(e, reader, false, context)?),
+                        b"properties"   => properties = read_properties(reader)?,
+                        _               => exhaust(reader)?,
+                    }
+                },
+                Ok(Event::End(ref e)) if e.name() == b"objectgroup" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "objectgroup")),
+                Err(e)          => return Err(xml_error(reader, e)),
                 _               => (),
-            }},
-            Ok(Event::Eof)  => break,
-            Err(e)          => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            _               => (),
+            }
+            buf.clear();
         }
     }
 
-    let kv = extract_attributes(e);
-    Objectgroup {
-        x:kv.get("x").unwrap_or(&"0".to_string()).parse::<f32>().unwrap(),
-        y:kv.get("y").unwrap_or(&"0".to_string()).parse::<f32>().unwrap(),
-        offsetx:kv.get("offsetx").unwrap_or(&"0".to_string()).parse::<i32>().unwrap(),
-        offsety:kv.get("offsety").unwrap_or(&"0".to_string()).parse::<i32>().unwrap(),
-        color: kv.get("color").unwrap_or(&String::new()).to_string(),
-        draworder: kv.get("draworder").map(|s| draworder_from_string(s)).unwrap_or(DrawOrder::TopDown),
-        id: kv.get("id").unwrap_or(&"0".to_string()).parse::<i64>().unwrap(),
-        name: kv.get("name").unwrap().to_string(),
-        opacity: kv.get("opacity").unwrap_or(&"1.0".to_string()).parse::<f32>().unwrap(),
-        visible: kv.get("visible").map(|s| s == "0").unwrap_or(true),
-        objects: objects,
-        properties: properties,
-    }
+    objectgroup_from_attributes(&attrs, objects, properties)
 }
 
-fn read_tile(e: &BytesStart, reader: &mut Reader<&[u8]>) -> Tile {
+fn objectgroup_from_attributes(attrs: &Attributes, objects: Vec<Object>, properties: Properties) -> Result<Objectgroup, TmxError> {
 
-    let mut buf = Vec::new();
+    Ok(Objectgroup {
+        x: attrs.number_or("x", 0.0)?,
+        y: attrs.number_or("y", 0.0)?,
+        offsetx: attrs.number_or("offsetx", 0)?,
+        offsety: attrs.number_or("offsety", 0)?,
+        tintcolor: attrs.value("tintcolor", Rgba::from_hex)?,
+        color: attrs.get("color").unwrap_or("").to_string(),
+        draworder: attrs.value("draworder", draworder_from_string)?.unwrap_or(DrawOrder::TopDown),
+        id: attrs.number_or("id", 0)?,
+        name: attrs.get("name").unwrap_or("").to_string(),
+        opacity: attrs.number_or("opacity", 1.0)?,
+        visible: attrs.bool_or("visible", true)?,
+        objects,
+        properties,
+    })
+}
+
+fn read_tile<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, context: &mut Context) -> Result<Tile, TmxError> {
 
+    let attrs = extract_attributes(reader, e)?;
     let mut image: Option<Image> = None;
+    let mut animation: Vec<Frame> = Vec::new();
+    let mut collision: Option<Objectgroup> = None;
+    let mut properties = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"animation"    => animation = read_animation(reader)?,
+                        b"objectgroup"  => collision = Some(read_objectgroup(e, reader, false, context)?),
+                        b"properties"   => properties = read_properties(reader)?,
+                        b"image"        => {
+                            image = Some(read_image(reader, e)?);
+                            exhaust(reader)?
+                        },
+                        _               => exhaust(reader)?,
+                    }
+                },
+                Ok(Event::Empty(ref e)) if e.name() == b"image" => image = Some(read_image(reader, e)?),
+                Ok(Event::Empty(ref e)) if e.name() == b"objectgroup" => collision = Some(read_objectgroup(e, reader, true, context)?),
+                Ok(Event::End(ref e)) if e.name() == b"tile" => break,
+                Err(e) => return Err(xml_error(reader, e)),
+                Ok(Event::Eof) => return Err(unexpected_eof(reader, "tile")),
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+
+    tile_from_attributes(&attrs, image, properties, animation, collision)
+}
+
+fn tile_from_attributes(attrs: &Attributes, image: Option<Image>, properties: Properties, animation: Vec<Frame>, collision: Option<Objectgroup>) -> Result<Tile, TmxError> {
+
+    Ok(Tile {
+        id: attrs.required_number("id")?,
+        terrain: attrs.value("terrain", terrain_from_string)?,
+        probability: attrs.number_or("probability", 1.0)?,
+        tile_type: attrs.string("class").or_else(|| attrs.string("type")),
+        image,
+        x: attrs.number_or("x", 0)?,
+        y: attrs.number_or("y", 0)?,
+        width: attrs.number("width")?,
+        height: attrs.number("height")?,
+        properties,
+        animation,
+        collision,
+    })
+}
+
+fn read_animation<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Frame>, TmxError> {
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut buf = Vec::new();
     loop {
         match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.name() {
-                    _ => exhaust(reader),
-                }
-            },
-            Ok(Event::Empty(ref e)) => {
-                match e.name() {
-                    b"image" => image = Some(read_image(e)),
-                    _ => exhaust(reader),
-                }
-                break;
-            },
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            Ok(Event::Eof) => break,
-            _ => (),
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::Empty(ref e)) if e.name() == b"frame" => frames.push(frame_from_attributes(&extract_attributes(reader, e)?)?),
+            Ok(Event::End(ref e)) if e.name() == b"animation" => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "animation")),
+            _               => (),
         }
+        buf.clear();
     }
 
-    let kv = extract_attributes(e);
-    Tile {
-        id: kv.get("id").unwrap().parse::<i64>().unwrap(),
-        terrain: kv.get("terrain").map(|s| s.to_string()),
-        probability: kv.get("probability").unwrap_or(&"1".to_string()).parse::<u32>().unwrap(),
-        tile_type: kv.get("tile_type").map(|s| s.to_string()),
-        image: image,
-    }
+    Ok(frames)
+}
+
+fn frame_from_attributes(attrs: &Attributes) -> Result<Frame, TmxError> {
+
+    Ok(Frame {
+        tileid: attrs.required_number("tileid")?,
+        duration: attrs.required_number("duration")?,
+    })
 }
 
-fn read_tileset(e: &BytesStart, reader: &mut Reader<&[u8]>) -> Tileset{
+fn read_tileset<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, firstgid: i64, context: &mut Context) -> Result<Tileset, TmxError> {
 
+    let attrs = extract_attributes(reader, e)?;
     let mut buf = Vec::new();
     let mut tiles = Vec::new();
     let mut tile_offset: Option<TileOffset> = None;
+    let mut image: Option<Image> = None;
+    let mut grid: Option<Grid> = None;
+    let mut transformations: Option<Transformations> = None;
+    let mut wangsets: Vec<WangSet> = Vec::new();
+    let mut terraintypes: Vec<TerrainType> = Vec::new();
+    let mut properties = Properties::default();
 
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => {
                 match e.name() {
-                    b"tile" => tiles.push(read_tile(e, reader)),
-                    _       => exhaust(reader),
+                    b"tile"         => tiles.push(read_tile(e, reader, false, context)?),
+                    b"properties"   => properties = read_properties(reader)?,
+                    b"wangsets"     => wangsets = read_wangsets(reader)?,
+                    b"terraintypes" => terraintypes = read_terraintypes(reader)?,
+                    b"image"        => {
+                        image = Some(read_image(reader, e)?);
+                        exhaust(reader)?
+                    },
+                    _               => exhaust(reader)?,
                 }
             },
             Ok(Event::Empty(ref e)) => {
                 match e.name() {
-                    b"tileoffset" => tile_offset = Some(read_tileoffset(e)),
-                    _ => exhaust(reader),
+                    b"tile"             => tiles.push(read_tile(e, reader, true, context)?),
+                    b"tileoffset"       => tile_offset = Some(tileoffset_from_attributes(&extract_attributes(reader, e)?)?),
+                    b"image"            => image = Some(read_image(reader, e)?),
+                    b"grid"             => grid = Some(grid_from_attributes(&extract_attributes(reader, e)?)?),
+                    b"transformations"  => transformations = Some(transformations_from_attributes(&extract_attributes(reader, e)?)?),
+                    _                   => (),
                 }
             },
-            Ok(Event::End(ref e)) => {match e.name() {
-                b"tileset" => break,
-                _ => (),
-            }},
-            Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Ok(Event::End(ref e)) if e.name() == b"tileset" => break,
+            Ok(Event::Eof) => return Err(unexpected_eof(reader, "tileset")),
+            Err(e) => return Err(xml_error(reader, e)),
             _ => (),
         }
+        buf.clear();
     }
 
-    let kv = extract_attributes(e);
-    Tileset {
-        firstgid: kv.get("firstgid").unwrap().parse::<i64>().unwrap(),
-        name: kv.get("name").unwrap().to_string(),
-        tilewidth: kv.get("tilewidth").unwrap().parse::<i32>().unwrap(),
-        tileheight: kv.get("tileheight").unwrap().parse::<i32>().unwrap(),
-        tilecount: kv.get("tilecount").unwrap().parse::<i32>().unwrap(),
-        columns: kv.get("columns").unwrap().parse::<i32>().unwrap(),
-        margin: 0,
-        source: kv.get("name").map(|s| s.to_string()),
-        spacing: 0,
-        tiles: tiles,
+    Ok(Tileset {
+        image,
+        tiles,
         tileoffset: tile_offset,
-    }
+        grid,
+        transformations,
+        wangsets,
+        terraintypes,
+        properties,
+        ..tileset_from_attributes(&attrs, firstgid)?
+    })
 }
 
-fn read_map(e: &BytesStart, reader: &mut Reader<&[u8]>) -> Map{
+/// Builds a tileset without any tiles, image or other content.
+fn tileset_from_attributes(attrs: &Attributes, firstgid: i64) -> Result<Tileset, TmxError> {
+
+    Ok(Tileset {
+        firstgid,
+        name: attrs.required("name")?.to_string(),
+        class: attrs.string("class"),
+        tilewidth: attrs.required_number("tilewidth")?,
+        tileheight: attrs.required_number("tileheight")?,
+        tilecount: attrs.required_number("tilecount")?,
+        columns: attrs.required_number("columns")?,
+        margin: attrs.number_or("margin", 0)?,
+        source: None,
+        spacing: attrs.number_or("spacing", 0)?,
+        objectalignment: attrs.value("objectalignment", objectalignment_from_string)?.unwrap_or(ObjectAlignment::Unspecified),
+        tilerendersize: attrs.value("tilerendersize", tilerendersize_from_string)?.unwrap_or(TileRenderSize::Tile),
+        fillmode: attrs.value("fillmode", fillmode_from_string)?.unwrap_or(FillMode::Stretch),
+        image: None,
+        tiles: Vec::new(),
+        tileoffset: None,
+        grid: None,
+        transformations: None,
+        wangsets: Vec::new(),
+        terraintypes: Vec::new(),
+        properties: Properties::default(),
+    })
+}
 
+fn grid_from_attributes(attrs: &Attributes) -> Result<Grid, TmxError> {
 
-    let mut buf = Vec::new();
+    Ok(Grid {
+        orientation: attrs.value("orientation", orientation_from_string)?.unwrap_or(Orientation::Orthogonal),
+        width: attrs.required_number("width")?,
+        height: attrs.required_number("height")?,
+    })
+}
+
+fn transformations_from_attributes(attrs: &Attributes) -> Result<Transformations, TmxError> {
+
+    Ok(Transformations {
+        hflip: attrs.bool_or("hflip", false)?,
+        vflip: attrs.bool_or("vflip", false)?,
+        rotate: attrs.bool_or("rotate", false)?,
+        preferuntransformed: attrs.bool_or("preferuntransformed", false)?,
+    })
+}
 
-    let mut tileset: Option<Tileset> = None;
-    let mut objectgroups: Vec<Objectgroup> = Vec::new();
-    let mut properties: Option<Vec<Property>> = None;
+fn read_wangsets<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<WangSet>, TmxError> {
 
+    let mut wangsets: Vec<WangSet> = Vec::new();
+    let mut buf = Vec::new();
     loop {
         match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.name() {
-                    b"objectgroup"  => objectgroups.push(read_objectgroup(e, reader)),
-                    b"tileset"      => tileset = Some(read_tileset(e, reader)),
-                    b"properties"   => properties = Some(read_properties(reader)),
-                    _               => exhaust(reader),
-                }
-            },
-            Err(e)          => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            Ok(Event::Eof)  => break,
+            Ok(Event::Start(ref e)) if e.name() == b"wangset" => wangsets.push(read_wangset(e, reader, false)?),
+            Ok(Event::Empty(ref e)) if e.name() == b"wangset" => wangsets.push(read_wangset(e, reader, true)?),
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == b"wangsets" => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "wangsets")),
             _               => (),
         }
+        buf.clear();
     }
 
-    let kv = extract_attributes(e);
-    Map {
-        version: kv.get("version").unwrap().to_string(),
-        orientation: orientation_from_string(kv.get("orientation").unwrap()),
-        renderorder: kv.get("renderorder").map(|s| renderorder_from_string(s)).unwrap_or(Renderorder::RightDown),
-        width: kv.get("width").unwrap().parse::<i32>().unwrap(),
-        height: kv.get("height").unwrap().parse::<i32>().unwrap(),
-        tilewidth: kv.get("tilewidth").unwrap().parse::<i32>().unwrap(),
-        tileheight: kv.get("tileheight").unwrap().parse::<i32>().unwrap(),
-        backgroundcolor: kv.get("backgroundcolor").unwrap().to_string(),
-        nextobjectid: kv.get("nextobjectid").unwrap().parse::<i32>().unwrap(),
-        staggeraxis: kv.get("staggeraxis").map(|s| staggeraxis_from_string(s)).unwrap_or(StaggerAxis::X),
-        staggerindex: kv.get("staggerindex").map(|s| staggerindex_from_string(s)).unwrap_or(StaggerIndex::Even),
-        objectgroups: objectgroups,
-        tileset: tileset.unwrap(),
-        properties: properties
-    }
+    Ok(wangsets)
 }
 
-fn extract_attributes(e: &BytesStart) -> HashMap<String, String> {
-    e.attributes().map(|a| {
-        let attribute = a.unwrap();
-        let key = attribute.key;
-        let mut value = attribute.value;
-        (str::from_utf8(key).unwrap().to_string().to_lowercase(), str::from_utf8(value.to_mut()).unwrap().to_string())
-    }).collect::<HashMap<String, String>>()
-}
+/// Tilesets written before Tiled 1.5 have separate `<wangcornercolor>` and `<wangedgecolor>` elements,
+/// they are read in document order like `<wangcolor>`.
+fn read_wangset<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool) -> Result<WangSet, TmxError> {
 
-fn exhaust(reader: &mut Reader<&[u8]>) {
-    let mut buf = Vec::new();
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref _e)) => {exhaust(reader)},
-            Ok(Event::Empty(ref _e)) => {exhaust(reader); break},
-            Ok(Event::End(ref _e)) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            _ => (),
+    let attrs = extract_attributes(reader, e)?;
+    let mut colors: Vec<WangColor> = Vec::new();
+    let mut tiles: Vec<WangTile> = Vec::new();
+    let mut properties = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"properties"   => properties = read_properties(reader)?,
+                        b"wangcolor" | b"wangcornercolor" | b"wangedgecolor" => {
+                            let color_attrs = extract_attributes(reader, e)?;
+                            colors.push(wangcolor_from_attributes(&color_attrs, read_element_properties(reader, e.name())?)?);
+                        },
+                        _               => exhaust(reader)?,
+                    }
+                },
+                Ok(Event::Empty(ref e)) => {
+                    match e.name() {
+                        b"wangcolor" | b"wangcornercolor" | b"wangedgecolor" =>
+                            colors.push(wangcolor_from_attributes(&extract_attributes(reader, e)?, Properties::default())?),
+                        b"wangtile"     => tiles.push(wangtile_from_attributes(&extract_attributes(reader, e)?)?),
+                        _               => (),
+                    }
+                },
+                Ok(Event::End(ref e)) if e.name() == b"wangset" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "wangset")),
+                Err(e)          => return Err(xml_error(reader, e)),
+                _               => (),
+            }
+            buf.clear();
         }
     }
+
+    wangset_from_attributes(&attrs, colors, tiles, properties)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::path::PathBuf;
-    use std::fs;
-    use super::*;
+fn wangset_from_attributes(attrs: &Attributes, colors: Vec<WangColor>, tiles: Vec<WangTile>, properties: Properties) -> Result<WangSet, TmxError> {
 
-    #[test]
-    fn test_read_sandbox() {
+    Ok(WangSet {
+        name: attrs.required("name")?.to_string(),
+        class: attrs.string("class"),
+        wangsettype: attrs.value("type", wangsettype_from_string)?.unwrap_or(WangSetType::Mixed),
+        tile: attrs.number("tile")?.filter(|tile: &i64| *tile >= 0),
+        colors,
+        tiles,
+        properties,
+    })
+}
 
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("resources/test");
-        d.push("sandbox.tmx");
+fn wangcolor_from_attributes(attrs: &Attributes, properties: Properties) -> Result<WangColor, TmxError> {
 
-        let contents = fs::read_to_string(d.as_os_str())
-            .expect("Something went wrong reading the file");
+    Ok(WangColor {
+        name: attrs.get("name").unwrap_or("").to_string(),
+        class: attrs.string("class"),
+        color: attrs.required_value("color", Rgba::from_hex)?,
+        tile: attrs.number("tile")?.filter(|tile: &i64| *tile >= 0),
+        probability: attrs.number_or("probability", 1.0)?,
+        properties,
+    })
+}
 
-        let map = Map::new(contents.as_str());
+fn wangtile_from_attributes(attrs: &Attributes) -> Result<WangTile, TmxError> {
 
-        test_map(&map);
+    Ok(WangTile {
+        tileid: attrs.required_number("tileid")?,
+        wangid: attrs.required_value("wangid", wangid_from_string)?,
+    })
+}
 
-        test_tileset(&map.tileset);
+fn read_terraintypes<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<TerrainType>, TmxError> {
 
-        test_objectgroups(map.objectgroups);
+    let mut terraintypes: Vec<TerrainType> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"terrain" => {
+                let attrs = extract_attributes(reader, e)?;
+                terraintypes.push(terraintype_from_attributes(&attrs, read_element_properties(reader, b"terrain")?)?);
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"terrain" =>
+                terraintypes.push(terraintype_from_attributes(&extract_attributes(reader, e)?, Properties::default())?),
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == b"terraintypes" => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "terraintypes")),
+            _               => (),
+        }
+        buf.clear();
     }
 
-    fn test_map(map: &Map) {
-        assert!(map.version == "1.0");
-        assert!(map.orientation == Orientation::Orthogonal);
-        assert!(map.renderorder == Renderorder::RightDown);
-        assert!(map.width == 79);
-        assert!(map.height == 45);
-        assert!(map.tilewidth == 32);
-        assert!(map.tileheight == 32);
-        assert!(map.backgroundcolor == "#27b99a");
-        assert!(map.nextobjectid == 203);
-        assert!(map.objectgroups.len() == 9);
-    }
+    Ok(terraintypes)
+}
 
-    fn test_tileset(tileset: &Tileset) {
-        assert!(tileset.firstgid == 1);
-        assert!(tileset.name == "objs");
-        assert!(tileset.tilewidth == 384);
-        assert!(tileset.tileheight == 332);
-        assert!(tileset.tilecount == 62);
-        assert!(tileset.columns == 0);
-        assert!(tileset.tiles.len() as i32 == tileset.tilecount);
+fn terraintype_from_attributes(attrs: &Attributes, properties: Properties) -> Result<TerrainType, TmxError> {
 
-        let tile: &Tile = tileset.tiles.get(0).unwrap();
+    Ok(TerrainType {
+        name: attrs.get("name").unwrap_or("").to_string(),
+        tile: attrs.number_or("tile", -1)?,
+        properties,
+    })
+}
 
-        assert!(tile.probability == 1);
-        assert!(tile.id == 0);
+/// Reads the content of an element that can only hold properties, up to its `end` tag.
+fn read_element_properties<R: BufRead>(reader: &mut Reader<R>, end: &[u8]) -> Result<Properties, TmxError> {
 
-        let image:&Image = tile.image.as_ref().unwrap();
-        assert!(image.source == "alter.png");
-        assert!(image.width == 160);
-        assert!(image.height.unwrap() == 192);
+    let mut properties = Properties::default();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"properties" => properties = read_properties(reader)?,
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == end => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, str::from_utf8(end).unwrap_or("element"))),
+            _               => (),
+        }
+        buf.clear();
     }
 
-    fn test_objectgroups(objectgroups: Vec<Objectgroup>) {
+    Ok(properties)
+}
 
-        assert!(objectgroups.get(0).unwrap().name == "parallax");
-        assert!(objectgroups.get(1).unwrap().name == "background");
-        assert!(objectgroups.get(2).unwrap().name == "ground");
-        assert!(objectgroups.get(3).unwrap().name == "castle");
-        assert!(objectgroups.get(4).unwrap().name == "castledeco");
-        assert!(objectgroups.get(5).unwrap().name == "shading");
-        assert!(objectgroups.get(6).unwrap().name == "game");
-        assert!(objectgroups.get(7).unwrap().name == "above");
-        assert!(objectgroups.get(8).unwrap().name == "bounds");
+fn read_xml_data<R: BufRead>(reader: &mut Reader<R>, end: &[u8]) -> Result<Vec<Gid>, TmxError> {
 
-        let objectgroup = objectgroups.get(2).unwrap();
+    let mut data: Vec<Gid> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::Empty(ref e)) if e.name() == b"tile" => data.push(Gid(extract_attributes(reader, e)?.number_or("gid", 0)?)),
+            Ok(Event::End(ref e)) if e.name() == end => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, str::from_utf8(end).unwrap_or("data"))),
+            _               => (),
+        }
+        buf.clear();
+    }
 
-        let object:&Object = objectgroup.objects.get(0).as_ref().unwrap();
+    Ok(data)
+}
 
-        assert!(object.id == 2);
-        assert!(object.gid.unwrap() == 31);
-        assert!(object.x == 0.0);
-        assert!(object.y == 1087.0);
-        assert!(object.width == 256.0);
-        assert!(object.height == 96.0);
+fn read_text<R: BufRead>(reader: &mut Reader<R>, end: &[u8]) -> Result<String, TmxError> {
+    let mut buf = Vec::new();
+    reader.read_text(end, &mut buf).map_err(|e| xml_error(reader, e))
+}
 
-        let properties: &Vec<Property> = object.properties.as_ref().unwrap();
-        assert!(properties.len() == 2);
+/// Decodes the tiles up to the closing `end` tag.
+///
+/// CSV and Base64 text is decoded straight from the buffer of the reader,
+/// tile data can make up most of a document and isn't copied into a string first.
+fn read_tile_data<R: BufRead>(reader: &mut Reader<R>, end: &[u8], data_encoding: DataEncoding, compression: Option<Compression>) -> Result<Vec<Gid>, TmxError> {
 
-        let property:&Property = properties.get(1).unwrap();
+    if data_encoding == DataEncoding::Xml {
+        return read_xml_data(reader, end);
+    }
 
-        assert!(property.name == "friction");
-        assert!(property.prop_type == PropertyType::Float);
-        assert!(property.value == "1");
+    let mut data: Vec<Gid> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Text(ref t)) => {
+                let text = t.unescaped().map_err(|e| xml_error(reader, e))?;
+                let text = str::from_utf8(&text).map_err(|e| xml_error(reader, quick_xml::Error::Utf8(e)))?;
+                let decoded = match data_encoding {
+                    DataEncoding::Base64 => encoding::decode_base64(text, compression)?,
+                    _ => encoding::decode_csv(text)?,
+                };
+                if data.is_empty() {
+                    data = decoded;
+                } else {
+                    data.extend(decoded);
+                }
+            },
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == end => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, str::from_utf8(end).unwrap_or("data"))),
+            _               => (),
+        }
+        buf.clear();
+    }
+
+    Ok(data)
+}
+
+fn read_chunks<R: BufRead>(reader: &mut Reader<R>, data_encoding: DataEncoding, compression: Option<Compression>) -> Result<Vec<Chunk>, TmxError> {
+
+    let mut chunks = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"chunk" => {
+                let chunk_attrs = extract_attributes(reader, e)?;
+                let data = read_tile_data(reader, b"chunk", data_encoding, compression)?;
+                chunks.push(chunk_from_attributes(&chunk_attrs, data)?);
+            },
+            Ok(Event::Start(_)) => exhaust(reader)?,
+            Ok(Event::End(ref e)) if e.name() == b"data" => break,
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "data")),
+            Err(e)          => return Err(xml_error(reader, e)),
+            _               => (),
+        }
+        buf.clear();
+    }
+
+    Ok(chunks)
+}
+
+fn chunk_from_attributes(attrs: &Attributes, data: Vec<Gid>) -> Result<Chunk, TmxError> {
+
+    Ok(Chunk {
+        x: attrs.required_number("x")?,
+        y: attrs.required_number("y")?,
+        width: attrs.required_number("width")?,
+        height: attrs.required_number("height")?,
+        data,
+    })
+}
+
+fn read_layer<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, infinite: bool) -> Result<Layer, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let mut data: Vec<Gid> = Vec::new();
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut encoding = DataEncoding::Xml;
+    let mut compression: Option<Compression> = None;
+    let mut properties = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"data"         => {
+                            let data_attrs = extract_attributes(reader, e)?;
+                            encoding = data_attrs.value("encoding", encoding_from_string)?.unwrap_or(DataEncoding::Xml);
+                            compression = data_attrs.value("compression", compression_from_string)?;
+
+                            if infinite {
+                                chunks = read_chunks(reader, encoding, compression)?;
+                            } else {
+                                data = read_tile_data(reader, b"data", encoding, compression)?;
+                            }
+                        },
+                        b"properties"   => properties = read_properties(reader)?,
+                        _               => exhaust(reader)?,
+                    }
+                },
+                Ok(Event::End(ref e)) if e.name() == b"layer" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "layer")),
+                Err(e)          => return Err(xml_error(reader, e)),
+                _               => (),
+            }
+            buf.clear();
+        }
+    }
+
+    Ok(Layer {
+        properties,
+        encoding,
+        compression,
+        ..layer_from_attributes(&attrs, data, chunks, infinite)?
+    })
+}
+
+/// Builds a layer holding the given tiles, stored as XML without properties.
+fn layer_from_attributes(attrs: &Attributes, data: Vec<Gid>, chunks: Vec<Chunk>, infinite: bool) -> Result<Layer, TmxError> {
+
+    let name = attrs.required("name")?.to_string();
+    let width: i32 = attrs.required_number("width")?;
+    let height: i32 = attrs.required_number("height")?;
+
+    let count = tile_count(width, height)
+        .ok_or_else(|| TmxError::InvalidTileData(format!("Layer {} has an invalid size of {}x{}", name, width, height)))?;
+    if !infinite && data.len() != count {
+        return Err(TmxError::InvalidTileData(format!("Layer {} has {} tiles but should have {}", name, data.len(), count)));
+    }
+
+    Ok(Layer {
+        id: attrs.number_or("id", 0)?,
+        name,
+        x: attrs.number_or("x", 0.0)?,
+        y: attrs.number_or("y", 0.0)?,
+        width,
+        height,
+        opacity: attrs.number_or("opacity", 1.0)?,
+        visible: attrs.bool_or("visible", true)?,
+        offsetx: attrs.number_or("offsetx", 0)?,
+        offsety: attrs.number_or("offsety", 0)?,
+        tintcolor: attrs.value("tintcolor", Rgba::from_hex)?,
+        properties: Properties::default(),
+        data,
+        chunks: Chunks::new(chunks)?,
+        infinite,
+        encoding: DataEncoding::Xml,
+        compression: None,
+    })
+}
+
+/// The number of cells of a layer or chunk, `None` if a size is negative or there are more cells than an `i32` can count.
+pub(crate) fn tile_count(width: i32, height: i32) -> Option<usize> {
+    match width.checked_mul(height) {
+        Some(count) if width >= 0 && height >= 0 => Some(count as usize),
+        _ => None,
+    }
+}
+
+fn read_imagelayer<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool) -> Result<Imagelayer, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let mut image: Option<Image> = None;
+    let mut properties = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"properties"   => properties = read_properties(reader)?,
+                        _               => exhaust(reader)?,
+                    }
+                },
+                Ok(Event::Empty(ref e)) if e.name() == b"image" => image = Some(read_image(reader, e)?),
+                Ok(Event::End(ref e)) if e.name() == b"imagelayer" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "imagelayer")),
+                Err(e)          => return Err(xml_error(reader, e)),
+                _               => (),
+            }
+            buf.clear();
+        }
+    }
+
+    imagelayer_from_attributes(&attrs, image, properties)
+}
+
+fn imagelayer_from_attributes(attrs: &Attributes, image: Option<Image>, properties: Properties) -> Result<Imagelayer, TmxError> {
+
+    Ok(Imagelayer {
+        id: attrs.number_or("id", 0)?,
+        name: attrs.required("name")?.to_string(),
+        offsetx: attrs.number_or("offsetx", 0)?,
+        offsety: attrs.number_or("offsety", 0)?,
+        x: attrs.number_or("x", 0.0)?,
+        y: attrs.number_or("y", 0.0)?,
+        opacity: attrs.number_or("opacity", 1.0)?,
+        visible: attrs.bool_or("visible", true)?,
+        tintcolor: attrs.value("tintcolor", Rgba::from_hex)?,
+        repeatx: attrs.bool_or("repeatx", false)?,
+        repeaty: attrs.bool_or("repeaty", false)?,
+        parallaxx: attrs.number_or("parallaxx", 1.0)?,
+        parallaxy: attrs.number_or("parallaxy", 1.0)?,
+        image,
+        properties,
+    })
+}
+
+fn apply_project_to_layers(layers: &mut [LayerKind], project: &Project) -> Result<(), TmxError> {
+    for layer in layers {
+        match layer {
+            LayerKind::Tile(layer) => project.resolve(&mut layer.properties)?,
+            LayerKind::Object(objectgroup) => {
+                project.resolve(&mut objectgroup.properties)?;
+                for object in &mut objectgroup.objects {
+                    project.resolve(&mut object.properties)?;
+                }
+            },
+            LayerKind::Image(imagelayer) => project.resolve(&mut imagelayer.properties)?,
+            LayerKind::Group(group) => {
+                project.resolve(&mut group.properties)?;
+                apply_project_to_layers(&mut group.layers, project)?;
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Reads any kind of layer, returns `None` if the element isn't a layer at all.
+fn read_layer_kind<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, context: &mut Context) -> Result<Option<LayerKind>, TmxError> {
+    Ok(Some(match e.name() {
+        b"layer"        => LayerKind::Tile(read_layer(e, reader, is_empty, context.infinite)?),
+        b"objectgroup"  => LayerKind::Object(read_objectgroup(e, reader, is_empty, context)?),
+        b"imagelayer"   => LayerKind::Image(read_imagelayer(e, reader, is_empty)?),
+        b"group"        => LayerKind::Group(read_group(e, reader, is_empty, context)?),
+        _               => return Ok(None),
+    }))
+}
+
+fn read_group<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, context: &mut Context) -> Result<Group, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let mut layers: Vec<LayerKind> = Vec::new();
+    let mut properties = Properties::default();
+
+    if !is_empty {
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name() {
+                        b"properties"   => properties = read_properties(reader)?,
+                        _               => match read_layer_kind(e, reader, false, context)? {
+                            Some(layer) => layers.push(layer),
+                            None        => exhaust(reader)?,
+                        },
+                    }
+                },
+                Ok(Event::Empty(ref e)) => layers.extend(read_layer_kind(e, reader, true, context)?),
+                Ok(Event::End(ref e)) if e.name() == b"group" => break,
+                Ok(Event::Eof)  => return Err(unexpected_eof(reader, "group")),
+                Err(e)          => return Err(xml_error(reader, e)),
+                _               => (),
+            }
+            buf.clear();
+        }
+    }
+
+    group_from_attributes(&attrs, layers, properties)
+}
+
+fn group_from_attributes(attrs: &Attributes, layers: Vec<LayerKind>, properties: Properties) -> Result<Group, TmxError> {
+
+    Ok(Group {
+        id: attrs.number_or("id", 0)?,
+        name: attrs.required("name")?.to_string(),
+        offsetx: attrs.number_or("offsetx", 0)?,
+        offsety: attrs.number_or("offsety", 0)?,
+        opacity: attrs.number_or("opacity", 1.0)?,
+        visible: attrs.bool_or("visible", true)?,
+        tintcolor: attrs.value("tintcolor", Rgba::from_hex)?,
+        properties,
+        layers,
+    })
+}
+
+/// Loads the `.tsx` file a `<tileset firstgid=".." source=".."/>` reference points to.
+fn read_external_tileset<R: BufRead>(reader: &Reader<R>, e: &BytesStart, loader: &dyn ResourceLoader) -> Result<Tileset, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+
+    load_tileset(attrs.required("source")?, attrs.required_number("firstgid")?, loader)
+}
+
+fn load_tileset(source: &str, firstgid: i64, loader: &dyn ResourceLoader) -> Result<Tileset, TmxError> {
+
+    let content = loader.load(source)
+        .map_err(|error| TmxError::Resource { path: source.to_string(), error })?;
+    // Templates used by the tiles are referenced relative to the tileset, not to the map.
+    let loader = &RelativeLoader { loader, base: source };
+    let content = str::from_utf8(&content)
+        .map_err(|e| TmxError::Xml { position: e.valid_up_to(), error: quick_xml::Error::Utf8(e) })?;
+
+    match Format::detect(content) {
+        Format::Xml => (),
+        #[cfg(feature = "json")]
+        Format::Json => return json::read_external_tileset(content, source, firstgid, loader),
+        #[cfg(not(feature = "json"))]
+        Format::Json => return Err(json_disabled()),
+    }
+
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"tileset" => {
+                let mut tileset = read_tileset(e, &mut reader, firstgid, &mut Context::new(loader))?;
+                tileset.source = Some(source.to_string());
+                return Ok(tileset);
+            },
+            Err(e) => return Err(xml_error(&reader, e)),
+            Ok(Event::Eof) => return Err(TmxError::MissingElement("tileset".to_string())),
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+fn read_map<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let mut buf = Vec::new();
+
+    let mut context = Context::new(loader);
+    context.infinite = attrs.bool_or("infinite", false)?;
+    let mut layers: Vec<LayerKind> = Vec::new();
+    let mut properties = Properties::default();
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"tileset"      => {
+                        let firstgid = extract_attributes(reader, e)?.required_number("firstgid")?;
+                        let tileset = read_tileset(e, reader, firstgid, &mut context)?;
+                        context.tilesets.push(tileset)
+                    },
+                    b"properties"   => properties = read_properties(reader)?,
+                    _               => match read_layer_kind(e, reader, false, &mut context)? {
+                        Some(layer) => layers.push(layer),
+                        None        => exhaust(reader)?,
+                    },
+                }
+            },
+            Ok(Event::Empty(ref e)) => {
+                match e.name() {
+                    b"tileset"      => context.tilesets.push(read_external_tileset(reader, e, loader)?),
+                    _               => layers.extend(read_layer_kind(e, reader, true, &mut context)?),
+                }
+            },
+            Ok(Event::End(ref e)) if e.name() == b"map" => break,
+            Err(e)          => return Err(xml_error(reader, e)),
+            Ok(Event::Eof)  => return Err(unexpected_eof(reader, "map")),
+            _               => (),
+        }
+        buf.clear();
+    }
+
+    map_from_attributes(&attrs, context.tilesets, layers, properties)
+}
+
+fn map_from_attributes(attrs: &Attributes, tilesets: Vec<Tileset>, layers: Vec<LayerKind>, properties: Properties) -> Result<Map, TmxError> {
+
+    Ok(Map {
+        version: attrs.required("version")?.to_string(),
+        orientation: attrs.required_value("orientation", orientation_from_string)?,
+        renderorder: attrs.value("renderorder", renderorder_from_string)?.unwrap_or(Renderorder::RightDown),
+        width: attrs.required_number("width")?,
+        height: attrs.required_number("height")?,
+        tilewidth: attrs.required_number("tilewidth")?,
+        tileheight: attrs.required_number("tileheight")?,
+        backgroundcolor: attrs.string("backgroundcolor"),
+        nextobjectid: attrs.number_or("nextobjectid", 1)?,
+        staggeraxis: attrs.value("staggeraxis", staggeraxis_from_string)?.unwrap_or(StaggerAxis::X),
+        staggerindex: attrs.value("staggerindex", staggerindex_from_string)?.unwrap_or(StaggerIndex::Even),
+        hexsidelength: attrs.number_or("hexsidelength", 0)?,
+        layers,
+        tilesets,
+        properties,
+        infinite: attrs.bool_or("infinite", false)?,
+    })
+}
+
+/// State shared by the readers of a single map.
+struct Context<'a> {
+    loader: &'a dyn ResourceLoader,
+    /// The tilesets of the map read so far, gids of templates get mapped into them.
+    tilesets: Vec<Tileset>,
+    /// Templates by the path they are referenced with, each one is only loaded once.
+    templates: HashMap<String, Template>,
+    /// Whether the map is infinite, its tile layers then hold chunks.
+    infinite: bool,
+}
+
+/// The object of a `.tx` file, with its gid already mapped to the tilesets of the map.
+struct Template {
+    attrs: Attributes,
+    properties: Properties,
+    shape: Option<ObjectShape>,
+}
+
+impl<'a> Context<'a> {
+    fn new(loader: &'a dyn ResourceLoader) -> Context<'a> {
+        Context { loader, tilesets: Vec::new(), templates: HashMap::new(), infinite: false }
+    }
+
+    fn template(&mut self, path: &str) -> Result<&Template, TmxError> {
+        if !self.templates.contains_key(path) {
+            let template = read_template(path, self)?;
+            self.templates.insert(path.to_string(), template);
+        }
+        Ok(&self.templates[path])
+    }
+
+    /// Maps a gid from the tileset at `source` starting at `firstgid` to the tilesets of the map.
+    /// Tiled always adds the tilesets used by templates to the map, if it's missing anyway it gets appended.
+    fn map_gid(&mut self, gid: Gid, firstgid: i64, source: &str) -> Result<Gid, TmxError> {
+        let existing = self.tilesets.iter()
+            .find(|t| t.source.as_deref().map(normalize_path) == Some(normalize_path(source)))
+            .map(|t| t.firstgid);
+
+        let map_firstgid = match existing {
+            Some(map_firstgid) => map_firstgid,
+            None => {
+                let next = self.tilesets.iter()
+                    .map(|t| t.firstgid + t.tiles.iter().map(|tile| tile.id + 1).fold(i64::from(t.tilecount), i64::max))
+                    .max()
+                    .unwrap_or(1);
+                self.tilesets.push(load_tileset(source, next, self.loader)?);
+                next
+            },
+        };
+
+        gid.with_id(gid.id() - firstgid + map_firstgid)
+            .ok_or_else(|| TmxError::InvalidNumber { element: "object".to_string(), attribute: "gid".to_string(), value: gid.to_string() })
+    }
+}
+
+/// Loads the `.tx` file at `path`, the tileset it references is relative to the template itself.
+fn read_template(path: &str, context: &mut Context) -> Result<Template, TmxError> {
+
+    let loader = context.loader;
+    let content = loader.load(path)
+        .map_err(|error| TmxError::Resource { path: path.to_string(), error })?;
+    let content = str::from_utf8(&content)
+        .map_err(|e| TmxError::Xml { position: e.valid_up_to(), error: quick_xml::Error::Utf8(e) })?;
+
+    match Format::detect(content) {
+        Format::Xml => (),
+        #[cfg(feature = "json")]
+        Format::Json => return json::read_template(content, path, context),
+        #[cfg(not(feature = "json"))]
+        Format::Json => return Err(json_disabled()),
+    }
+
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut tileset: Option<(i64, String)> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) if e.name() == b"tileset" => {
+                let attrs = extract_attributes(&reader, e)?;
+                tileset = Some((attrs.required_number("firstgid")?, join_path(path, attrs.required("source")?)));
+            },
+            Ok(Event::Start(ref e)) if e.name() == b"object" =>
+                return read_template_object(e, &mut reader, false, tileset.as_ref(), context),
+            Ok(Event::Empty(ref e)) if e.name() == b"object" =>
+                return read_template_object(e, &mut reader, true, tileset.as_ref(), context),
+            Err(e) => return Err(xml_error(&reader, e)),
+            Ok(Event::Eof) => return Err(TmxError::MissingElement("object".to_string())),
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+fn read_template_object<R: BufRead>(e: &BytesStart, reader: &mut Reader<R>, is_empty: bool, tileset: Option<&(i64, String)>, context: &mut Context) -> Result<Template, TmxError> {
+
+    let attrs = extract_attributes(reader, e)?;
+    let (properties, shape) = read_object_content(reader, is_empty)?;
+
+    template_from_object(attrs, properties, shape, tileset, context)
+}
+
+/// `tileset` is the one referenced by the template file, its gids get mapped into the tilesets of the map.
+fn template_from_object(mut attrs: Attributes, properties: Properties, shape: Option<ObjectShape>, tileset: Option<&(i64, String)>, context: &mut Context) -> Result<Template, TmxError> {
+
+    if let (Some(gid), Some((firstgid, source))) = (attrs.number("gid")?.map(Gid), tileset) {
+        let gid = context.map_gid(gid, *firstgid, source)?;
+        attrs.values.insert("gid".to_string(), gid.to_string());
+    }
+
+    Ok(Template { attrs, properties, shape })
+}
+
+/// Resolves `path`, which is relative to the file at `base`, to the form it has to be passed to the loader in.
+fn join_path(base: &str, path: &str) -> String {
+    match base.rfind('/') {
+        Some(index) if !path.starts_with('/') => normalize_path(&format!("{}/{}", &base[..index], path)),
+        _ => normalize_path(path),
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "." => (),
+            ".." if components.last().is_some_and(|c| *c != ".." && !c.is_empty()) => {
+                components.pop();
+            },
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// The attributes of a single element, keyed by their lowercased name.
+///
+/// All accessors report failures with the element and attribute they concern.
+#[derive(Clone)]
+struct Attributes {
+    element: String,
+    values: HashMap<String, String>,
+}
+
+impl Attributes {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    fn string(&self, name: &str) -> Option<String> {
+        self.values.get(name).cloned()
+    }
+
+    fn required(&self, name: &str) -> Result<&str, TmxError> {
+        self.get(name).ok_or_else(|| self.missing(name))
+    }
+
+    fn number<T: FromStr>(&self, name: &str) -> Result<Option<T>, TmxError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => value.trim().parse::<T>().map(Some).map_err(|_| TmxError::InvalidNumber {
+                element: self.element.clone(),
+                attribute: name.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    fn number_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, TmxError> {
+        Ok(self.number(name)?.unwrap_or(default))
+    }
+
+    fn required_number<T: FromStr>(&self, name: &str) -> Result<T, TmxError> {
+        self.number(name)?.ok_or_else(|| self.missing(name))
+    }
+
+    fn bool_or(&self, name: &str, default: bool) -> Result<bool, TmxError> {
+        Ok(self.value(name, bool_from_string)?.unwrap_or(default))
+    }
+
+    /// Converts an attribute with a fixed set of allowed values, `convert` returns `None` for unknown ones.
+    fn value<T>(&self, name: &str, convert: fn(&str) -> Option<T>) -> Result<Option<T>, TmxError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => convert(value).map(Some).ok_or_else(|| self.unknown_value(name, value)),
+        }
+    }
+
+    fn required_value<T>(&self, name: &str, convert: fn(&str) -> Option<T>) -> Result<T, TmxError> {
+        self.value(name, convert)?.ok_or_else(|| self.missing(name))
+    }
+
+    fn missing(&self, name: &str) -> TmxError {
+        TmxError::MissingAttribute {
+            element: self.element.clone(),
+            attribute: name.to_string(),
+        }
+    }
+
+    fn unknown_value(&self, name: &str, value: &str) -> TmxError {
+        TmxError::UnknownValue {
+            element: self.element.clone(),
+            attribute: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Has to be called right after `reader` read the tag, errors in the attributes are reported at the end of the tag.
+fn extract_attributes<R: BufRead>(reader: &Reader<R>, e: &BytesStart) -> Result<Attributes, TmxError> {
+    let element = String::from_utf8_lossy(e.name()).to_string();
+    let mut values = HashMap::new();
+
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(|error| xml_error(reader, error))?;
+        let value = attribute.unescaped_value().map_err(|error| xml_error(reader, error))?;
+        values.insert(
+            String::from_utf8_lossy(attribute.key).to_lowercase(),
+            String::from_utf8_lossy(&value).to_string(),
+        );
+    }
+
+    Ok(Attributes { element, values })
+}
+
+#[cfg(not(feature = "json"))]
+fn json_disabled() -> TmxError {
+    TmxError::InvalidJson("Reading JSON files requires the json feature".to_string())
+}
+
+fn xml_error<R: BufRead>(reader: &Reader<R>, error: quick_xml::Error) -> TmxError {
+    TmxError::Xml { position: reader.buffer_position(), error }
+}
+
+fn unexpected_eof<R: BufRead>(reader: &Reader<R>, element: &str) -> TmxError {
+    xml_error(reader, quick_xml::Error::UnexpectedEof(element.to_string()))
+}
+
+/// Skips everything up to and including the end tag of an element whose start tag was just read.
+fn exhaust<R: BufRead>(reader: &mut Reader<R>) -> Result<(), TmxError> {
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref _e)) => exhaust(reader)?,
+            Ok(Event::End(ref _e)) => return Ok(()),
+            Ok(Event::Eof) => return Err(unexpected_eof(reader, "element")),
+            Err(e) => return Err(xml_error(reader, e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn test_read_sandbox() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("sandbox.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        test_map(&map);
+
+        assert!(map.tilesets.len() == 1);
+        test_tileset(map.tilesets.first().unwrap());
+
+        test_objectgroups(map.object_layers().map(|l| l.layer).collect());
+    }
+
+    fn test_map(map: &Map) {
+        assert!(map.version == "1.0");
+        assert!(map.orientation == Orientation::Orthogonal);
+        assert!(map.renderorder == Renderorder::RightDown);
+        assert!(map.width == 79);
+        assert!(map.height == 45);
+        assert!(map.tilewidth == 32);
+        assert!(map.tileheight == 32);
+        assert!(map.backgroundcolor == Some("#27b99a".to_string()));
+        assert!(map.nextobjectid == 203);
+        assert!(map.object_layers().count() == 9);
+        assert!(map.object_layers().map(|l| l.z).collect::<Vec<usize>>() == (0..9).collect::<Vec<usize>>());
+        assert!(!map.object_layers().last().unwrap().values.visible);
+    }
+
+    fn test_tileset(tileset: &Tileset) {
+        assert!(tileset.firstgid == 1);
+        assert!(tileset.name == "objs");
+        assert!(tileset.tilewidth == 384);
+        assert!(tileset.tileheight == 332);
+        assert!(tileset.tilecount == 62);
+        assert!(tileset.columns == 0);
+        assert!(tileset.tiles.len() as i32 == tileset.tilecount);
+
+        let tile: &Tile = tileset.tiles.first().unwrap();
+
+        assert!(tile.probability == 1.0);
+        assert!(tile.id == 0);
+
+        let image:&Image = tile.image.as_ref().unwrap();
+        assert!(image.source == "alter.png");
+        assert!(image.width == 160);
+        assert!(image.height.unwrap() == 192);
+    }
+
+    fn test_objectgroups(objectgroups: Vec<&Objectgroup>) {
+
+        assert!(objectgroups.first().unwrap().name == "parallax");
+        assert!(objectgroups.get(1).unwrap().name == "background");
+        assert!(objectgroups.get(2).unwrap().name == "ground");
+        assert!(objectgroups.get(3).unwrap().name == "castle");
+        assert!(objectgroups.get(4).unwrap().name == "castledeco");
+        assert!(objectgroups.get(5).unwrap().name == "shading");
+        assert!(objectgroups.get(6).unwrap().name == "game");
+        assert!(objectgroups.get(7).unwrap().name == "above");
+        assert!(objectgroups.get(8).unwrap().name == "bounds");
+
+        let objectgroup = objectgroups.get(2).unwrap();
+
+        let object:&Object = objectgroup.objects.first().as_ref().unwrap();
+
+        assert!(object.id == 2);
+        assert!(object.gid.unwrap().id() == 31);
+        assert!(object.x == 0.0);
+        assert!(object.y == 1087.0);
+        assert!(object.width == 256.0);
+        assert!(object.height == 96.0);
+
+        let properties: &Properties = &object.properties;
+        assert!(properties.len() == 2);
+        assert!(properties.get_float("friction") == Some(1.0));
+        assert!(properties.get_string("bodyType") == Some("static"));
+        assert!(properties.get_int("friction").is_none());
+        assert!(properties.get("missing").is_none());
+
+        let property: &Property = properties.iter().nth(1).unwrap();
+
+        assert!(property.name == "friction");
+        assert!(property.value.property_type() == PropertyType::Float);
+
+        let objectgroup_parallax = objectgroups.first().unwrap();
+        let object_flippedcloud:&Object = objectgroup_parallax.objects.get(2).as_ref().unwrap();
+
+        assert!(object_flippedcloud.id == 91);
+        assert!(object_flippedcloud.gid.unwrap().id() == 7);
+        assert!(object_flippedcloud.x == 373.939);
+        assert!(object_flippedcloud.y == 627.121);
+        assert!(object_flippedcloud.width == 384.0);
+        assert!(!object_flippedcloud.gid.unwrap().flipped_vertically());
+        assert!(!object_flippedcloud.gid.unwrap().flipped_diagonally());
+        assert!(object_flippedcloud.gid.unwrap().flipped_horizontally());
+    }
+
+    #[test]
+    fn test_read_desert() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("desert.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        assert!(map.version == "1.0");
+        assert!(map.orientation == Orientation::Orthogonal);
+        assert!(map.renderorder == Renderorder::RightDown);
+        assert!(map.width == 40);
+        assert!(map.height == 40);
+        assert!(map.tilewidth == 32);
+        assert!(map.tileheight == 32);
+        assert!(map.tile_layers().count() == 2);
+
+        test_layers(&map.tile_layers().map(|l| l.layer).collect::<Vec<&Layer>>());
+    }
+
+    #[test]
+    #[cfg(all(feature = "zlib", feature = "gzip", feature = "zstd"))]
+    fn test_read_encodings() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("encodings.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        assert!(map.tile_layers().count() == 6);
+
+        for name in &["xml", "csv", "base64", "zlib", "gzip", "zstd"] {
+            let layer = map.tile_layers().find(|l| l.layer.name == *name).unwrap().layer;
+
+            assert!(layer.data.len() == 12 * 8, "{}", name);
+            for y in 0..8 {
+                for x in 0..12 {
+                    assert!(layer.gid_at(x, y) == Some(((x * 5 + y * 11) % 37) as i64), "{}", name);
+                }
+            }
+            assert!(layer.data[3 * 12 + 2].flipped_vertically(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_read_tilesets() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("tilesets.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        assert!(map.tilesets.len() == 3);
+        assert!(map.tilesets.iter().map(|t| t.firstgid).collect::<Vec<i64>>() == vec![1, 9, 15]);
+
+        let layer = map.tile_layers().next().unwrap().layer;
+        let resolve = |x, y| {
+            let tile_ref = map.tile_for_gid(layer.gid_at(x, y).unwrap())?;
+            Some((tile_ref.tileset.name.clone(), tile_ref.id, tile_ref.tile.is_some()))
+        };
+
+        assert!(resolve(0, 0) == Some(("terrain".to_string(), 0, false)));
+        assert!(resolve(1, 0) == Some(("terrain".to_string(), 7, false)));
+        assert!(resolve(2, 0) == Some(("props".to_string(), 0, true)));
+        assert!(resolve(3, 0) == Some(("props".to_string(), 5, true)));
+        assert!(resolve(0, 1) == Some(("items".to_string(), 0, false)));
+        assert!(resolve(1, 1) == Some(("items".to_string(), 3, false)));
+        assert!(resolve(2, 1).is_none());
+        assert!(resolve(3, 1) == Some(("terrain".to_string(), 2, false)));
+
+        let torch = map.tile_for_gid(14).unwrap().tile.unwrap();
+        assert!(torch.image.as_ref().unwrap().source == "torch.png");
+        // The props only hold the tiles 0 and 5, the ids between them belong to no tile.
+        assert!(map.tile_for_gid(10).is_none());
+        assert!(map.tile_for_gid(13).is_none());
+
+        assert!(map.tile_for_gid(19).is_none());
+    }
+
+    #[test]
+    fn test_source_rect() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("tileset_attributes.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        let dungeon = &map.tilesets[0];
+        assert!(dungeon.source_rect(0) == Some(Rect { x: 1, y: 1, width: 32, height: 32 }));
+        assert!(dungeon.source_rect(3) == Some(Rect { x: 103, y: 1, width: 32, height: 32 }));
+        assert!(dungeon.source_rect(6) == Some(Rect { x: 69, y: 35, width: 32, height: 32 }));
+        assert!(dungeon.source_rect(11) == Some(Rect { x: 103, y: 69, width: 32, height: 32 }));
+        assert!(dungeon.source_rect(12).is_none());
+        assert!(dungeon.source_rect(-1).is_none());
+        assert!(dungeon.tile_image(6).unwrap().source == "dungeon.png");
+
+        let props = &map.tilesets[1];
+        assert!(props.source_rect(0) == Some(Rect { x: 32, y: 16, width: 64, height: 48 }));
+        assert!(props.source_rect(1) == Some(Rect { x: 0, y: 0, width: 32, height: 64 }));
+        assert!(props.source_rect(2).is_none());
+        assert!(props.tile_image(0).unwrap().source == "sheet.png");
+        assert!(props.tile_image(2).is_none());
+    }
+
+    #[test]
+    fn test_read_infinite() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("infinite.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        assert!(map.infinite);
+
+        let layers: Vec<&Layer> = map.tile_layers().map(|l| l.layer).collect();
+        let ground = layers[0];
+        assert!(ground.infinite);
+        assert!(ground.data.is_empty());
+        assert!(ground.chunks.len() == 3);
+        assert!(ground.chunks.iter().map(|c| (c.x, c.y)).collect::<Vec<(i32, i32)>>() == vec![(-4, -8), (0, 0), (8, -4)]);
+
+        assert!(ground.gid_at(-4, -8) == Some(1));
+        assert!(ground.gid_at(-3, -7) == Some(2));
+        assert!(ground.gid_at(-1, -5) == Some(4));
+        assert!(ground.gid_at(-2, -8) == Some(0));
+        assert!(ground.gid_at(3, 3) == Some(6));
+        assert!(ground.raw_gid_at(3, 3).unwrap().flipped_horizontally());
+        assert!(ground.gid_at(11, -1) == Some(7));
+        assert!(ground.gid_at(-100, 250) == Some(0));
+        assert!(ground.gid_at(1, 1) == Some(0));
+
+        assert!(ground.chunks.chunk_at(-1, -5).unwrap().x == -4);
+        assert!(ground.chunks.chunk_at(4, 0).is_none());
+        assert!(ground.bounds() == Rect { x: -4, y: -8, width: 16, height: 12 });
+
+        let visible = ground.chunks.intersecting(Rect { x: -2, y: -6, width: 4, height: 8 }).map(|c| (c.x, c.y)).collect::<Vec<(i32, i32)>>();
+        assert!(visible == vec![(-4, -8), (0, 0)]);
+        assert!(ground.chunks.intersecting(Rect { x: i32::MAX - 10, y: i32::MAX - 10, width: i32::MAX, height: i32::MAX }).count() == 0);
+        assert!(ground.chunks.intersecting(Rect { x: i32::MIN, y: i32::MIN, width: i32::MAX, height: i32::MAX }).count() == 1);
+
+        let tiles: Vec<(i32, i32, Gid)> = ground.tiles().collect();
+        assert!(tiles.len() == 4 + 12 + 1);
+        assert!(tiles[0] == (-4, -8, Gid(1)));
+        assert!(tiles[16] == (11, -1, Gid(7)));
+
+        let details = layers[1];
+        assert!(details.gid_at(-4, 0) == Some(8));
+        assert!(details.gid_at(-1, 3) == Some(3));
+        assert!(details.bounds() == Rect { x: -4, y: 0, width: 4, height: 4 });
+
+        let empty = layers[2];
+        assert!(empty.chunks.is_empty());
+        assert!(empty.gid_at(0, 0) == Some(0));
+        assert!(empty.bounds() == Rect { x: 0, y: 0, width: 0, height: 0 });
+        assert!(empty.tiles().next().is_none());
+
+        let finite = Map::parse(&format!("<map {}><layer name=\"a\" width=\"2\" height=\"1\"><data encoding=\"csv\">0,3</data></layer></map>", MAP_ATTRIBUTES)).unwrap();
+        let layer = finite.tile_layers().next().unwrap().layer;
+        assert!(!finite.infinite && !layer.infinite);
+        assert!(layer.bounds() == Rect { x: 0, y: 0, width: 2, height: 1 });
+        assert!(layer.tiles().collect::<Vec<(i32, i32, Gid)>>() == vec![(1, 0, Gid(3))]);
+        assert!(layer.gid_at(-1, 0).is_none());
+    }
+
+    #[test]
+    fn test_read_animations() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("animations.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        let fire = &map.tilesets[0];
+        assert!(fire.tiles.len() == 2);
+        assert!(fire.tile(0).unwrap().animation == vec![
+            Frame { tileid: 0, duration: 100 },
+            Frame { tileid: 1, duration: 100 },
+            Frame { tileid: 2, duration: 200 },
+        ]);
+        assert!(fire.tile(3).unwrap().animation.is_empty());
+
+        let torch = map.tilesets[1].tile(5).unwrap();
+        assert!(torch.image.as_ref().unwrap().source == "torch.png");
+        assert!(torch.animation.len() == 2);
+        assert!(map.tilesets[1].tile(6).unwrap().image.as_ref().unwrap().source == "torch2.png");
+
+        assert!(fire.tile(0).unwrap().frame_at(550).unwrap().tileid == 1);
+        assert!(fire.tile(3).unwrap().frame_at(450).is_none());
+
+        let mut clock = AnimationClock::new(&map);
+        assert!(clock.current_tile_ids() == vec![(1, 0), (14, 5)].into_iter().collect());
+        assert!(clock.current_gid(1) == 1);
+        assert!(clock.current_gid(4) == 4);
+
+        clock.advance(150);
+        assert!(clock.current_tile_ids() == vec![(1, 1), (14, 6)].into_iter().collect());
+        assert!(clock.current_gid(1) == 2);
+        assert!(clock.current_gid(14) == 15);
+
+        clock.advance(100);
+        assert!(clock.elapsed() == 250);
+        assert!(clock.current_gid(1) == 3);
+        assert!(clock.current_gid(14) == 15);
+
+        clock.set_elapsed(400);
+        assert!(clock.current_gid(1) == 1);
+        assert!(clock.current_gid(14) == 14);
+    }
+
+    #[test]
+    fn test_read_tileset_attributes() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("tileset_attributes.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        let dungeon = &map.tilesets[0];
+        assert!(dungeon.source.is_none());
+        assert!(dungeon.class == Some("Walls".to_string()));
+        assert!(dungeon.spacing == 2);
+        assert!(dungeon.margin == 1);
+        assert!(dungeon.objectalignment == ObjectAlignment::Bottom);
+        assert!(dungeon.tilerendersize == TileRenderSize::Grid);
+        assert!(dungeon.fillmode == FillMode::PreserveAspectFit);
+        assert!(dungeon.tileoffset.as_ref().unwrap().y == 4);
+        assert!(dungeon.properties.get_bool("walkable") == Some(false));
+
+        let grid = dungeon.grid.as_ref().unwrap();
+        assert!(grid.orientation == Orientation::Isometric);
+        assert!(grid.width == 64);
+        assert!(grid.height == 32);
+
+        let transformations = dungeon.transformations.as_ref().unwrap();
+        assert!(transformations.hflip);
+        assert!(!transformations.vflip);
+        assert!(transformations.rotate);
+        assert!(transformations.preferuntransformed);
+
+        let image = dungeon.image.as_ref().unwrap();
+        assert!(image.source == "dungeon.png");
+        assert!(image.trans == Some("ff00ff".to_string()));
+        assert!(image.width == 137);
+        assert!(image.height == Some(104));
+
+        let wall = dungeon.tile(1).unwrap();
+        assert!(wall.tile_type == Some("Wall".to_string()));
+        assert!(wall.probability == 0.5);
+        assert!(wall.properties.get_int("height") == Some(2));
+        assert!(dungeon.tile(2).unwrap().tile_type == Some("Door".to_string()));
+
+        let props = &map.tilesets[1];
+        assert!(props.class.is_none());
+        assert!(props.spacing == 0);
+        assert!(props.margin == 0);
+        assert!(props.objectalignment == ObjectAlignment::Unspecified);
+        assert!(props.tilerendersize == TileRenderSize::Tile);
+        assert!(props.fillmode == FillMode::Stretch);
+        assert!(props.image.is_none());
+        assert!(props.transformations.is_none());
+        assert!(props.grid.as_ref().unwrap().orientation == Orientation::Orthogonal);
+
+        let sheet = props.tile(0).unwrap();
+        assert!(sheet.image.as_ref().unwrap().source == "sheet.png");
+        assert!(sheet.x == 32);
+        assert!(sheet.y == 16);
+        assert!(sheet.width == Some(64));
+        assert!(sheet.height == Some(48));
+
+        let barrel = props.tile(1).unwrap();
+        assert!(barrel.x == 0 && barrel.y == 0);
+        assert!(barrel.width.is_none() && barrel.height.is_none());
+        assert!(barrel.tile_type.is_none());
+    }
+
+    #[test]
+    fn test_collision_shapes() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("collision.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+
+        let blocks = &map.tilesets[0];
+        let collision = blocks.tile(0).unwrap().collision.as_ref().unwrap();
+        assert!(collision.draworder == DrawOrder::Index);
+        assert!(collision.objects.len() == 1);
+        assert!(collision.objects[0].y == 16.0);
+        assert!(blocks.tile(1).unwrap().collision.as_ref().unwrap().objects.len() == 2);
+        assert!(blocks.tile(2).unwrap().collision.as_ref().unwrap().objects.is_empty());
+        assert!(blocks.tile(3).is_none());
+
+        let close = |shape: &WorldShape, expected: &[(f32, f32)]| match shape {
+            WorldShape::Polygon(points) | WorldShape::Polyline(points) => points.len() == expected.len()
+                && points.iter().zip(expected).all(|(a, b)| (a.0 - b.0).abs() < 0.001 && (a.1 - b.1).abs() < 0.001),
+            _ => false,
+        };
+
+        let colliders = map.collision_shapes();
+        assert!(colliders.len() == 5);
+
+        assert!(colliders[0].gid == 1);
+        assert!(match colliders[0].source { ColliderSource::Cell { x, y, .. } => (x, y) == (0, 0), _ => false });
+        assert!(close(&colliders[0].shape, &[(0.0, 16.0), (32.0, 16.0), (32.0, 32.0), (0.0, 32.0)]));
+
+        // Flipped vertically the bottom half becomes the top half.
+        assert!(colliders[1].gid == 1);
+        assert!(close(&colliders[1].shape, &[(32.0, 16.0), (64.0, 16.0), (64.0, 0.0), (32.0, 0.0)]));
+
+        assert!(colliders[2].shape == WorldShape::Ellipse { center: (16.0, 48.0), radiusx: 8.0, radiusy: 8.0, rotation: 0.0 });
+        assert!(close(&colliders[3].shape, &[(0.0, 32.0), (32.0, 64.0)]));
+
+        // Twice the size of the tile, rotated around its bottom left corner and moved by the layer offset.
+        assert!(match colliders[4].source { ColliderSource::Object { object, .. } => object.id == 1, _ => false });
+        assert!(close(&colliders[4].shape, &[(142.0, 200.0), (142.0, 264.0), (110.0, 264.0), (110.0, 200.0)]));
+    }
+
+    #[test]
+    fn test_read_external_tileset_from_filesystem() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+
+        let contents = fs::read_to_string(d.join("external.tmx"))
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse_with_loader(contents.as_str(), &FileSystemLoader::new(d)).unwrap();
+
+        test_external_tilesets(&map);
+    }
+
+    #[test]
+    fn test_read_external_tileset_from_memory() {
+
+        let mut resources: HashMap<&str, &[u8]> = HashMap::new();
+        resources.insert("tilesets/dungeon.tsx", include_bytes!("../resources/test/tilesets/dungeon.tsx"));
+
+        let map = Map::parse_with_loader(include_str!("../resources/test/external.tmx"), &resources).unwrap();
+
+        test_external_tilesets(&map);
+    }
+
+    #[test]
+    fn test_read_external_tileset_without_loader() {
+        match Map::parse(include_str!("../resources/test/external.tmx")) {
+            Err(TmxError::Resource { path, .. }) => assert!(path == "tilesets/dungeon.tsx"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn test_external_tilesets(map: &Map) {
+        assert!(map.tilesets.len() == 2);
+
+        let dungeon = map.tilesets.first().unwrap();
+        assert!(dungeon.firstgid == 1);
+        assert!(dungeon.source.as_ref().unwrap() == "tilesets/dungeon.tsx");
+        assert!(dungeon.name == "dungeon");
+        assert!(dungeon.tilewidth == 16);
+        assert!(dungeon.tileheight == 16);
+        assert!(dungeon.tilecount == 64);
+        assert!(dungeon.columns == 8);
+        assert!(dungeon.tileoffset.as_ref().unwrap().y == 4);
+
+        let embedded = map.tilesets.get(1).unwrap();
+        assert!(embedded.firstgid == 65);
+        assert!(embedded.source.is_none());
+
+        let layer = map.tile_layers().next().unwrap().layer;
+        assert!(map.tile_for_gid(layer.gid_at(1, 0).unwrap()).unwrap().tileset.name == "dungeon");
+        assert!(map.tile_for_gid(layer.gid_at(2, 0).unwrap()).unwrap().tileset.name == "embedded");
+    }
+
+    #[test]
+    fn test_read_external_tileset_with_template() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+
+        let contents = fs::read_to_string(d.join("crates.tmx"))
+            .expect("Something went wrong reading the file");
+
+        // The template is referenced relative to the tileset, which lives in a directory of its own.
+        let map = Map::parse_with_loader(contents.as_str(), &FileSystemLoader::new(&d)).unwrap();
+        let tile = map.tile_for_gid(2).unwrap();
+        let hitbox = &tile.tile.unwrap().collision.as_ref().unwrap().objects[0];
+        assert!(hitbox.template == Some("../templates/hitbox.tx".to_string()));
+        assert!(hitbox.name == Some("hitbox".to_string()));
+        assert!(hitbox.x == 2.0 && hitbox.y == 10.0);
+        assert!(hitbox.width == 12.0 && hitbox.height == 6.0);
+
+        let mut resources: HashMap<&str, &[u8]> = HashMap::new();
+        resources.insert("tilesets/crates.tsx", include_bytes!("../resources/test/tilesets/crates.tsx"));
+        resources.insert("templates/hitbox.tx", include_bytes!("../resources/test/templates/hitbox.tx"));
+
+        let map = Map::parse_with_loader(contents.as_str(), &resources).unwrap();
+        assert!(map.tile_for_gid(2).unwrap().tile.unwrap().collision.as_ref().unwrap().objects[0].width == 12.0);
+    }
+
+    #[test]
+    fn test_read_templates() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+
+        let contents = fs::read_to_string(d.join("templates.tmx"))
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse_with_loader(contents.as_str(), &FileSystemLoader::new(d)).unwrap();
+        let objects = &map.object_layers().next().unwrap().layer.objects;
+        assert!(objects.len() == 5);
+
+        let orc = &objects[0];
+        assert!(orc.id == 1);
+        assert!(orc.template == Some("templates/orc.tx".to_string()));
+        assert!(orc.name == Some("orc".to_string()));
+        assert!(orc.object_type == Some("enemy".to_string()));
+        assert!(orc.x == 16.0 && orc.y == 32.0);
+        assert!(orc.width == 16.0 && orc.height == 16.0);
+        assert!(orc.gid == Some(Gid(21)));
+        assert!(orc.properties.get_int("hp") == Some(10));
+        assert!(orc.properties.get_float("speed") == Some(1.5));
+
+        let chief = &objects[1];
+        assert!(chief.name == Some("orc chief".to_string()));
+        assert!(chief.width == 32.0);
+        assert!(chief.gid == Some(Gid(21)));
+        assert!(chief.properties.get_int("hp") == Some(40));
+        assert!(chief.properties.get_float("speed") == Some(1.5));
+
+        let chest = &objects[2];
+        assert!(chest.gid.is_none());
+        assert!(chest.rotation == 90.0);
+        assert!(chest.shape == ObjectShape::Ellipse);
+        assert!(chest.properties.get_int("gold") == Some(5));
+
+        let opened = &objects[3];
+        assert!(opened.rotation == 0.0);
+        assert!(opened.shape == ObjectShape::Point);
+
+        // The tileset of the coin isn't part of the map, it gets appended after the last one.
+        let coin = &objects[4];
+        assert!(map.tilesets.len() == 3);
+        assert!(map.tilesets[2].source == Some("tilesets/items.tsx".to_string()));
+        assert!(map.tilesets[2].firstgid == 74);
+        assert!(coin.gid.unwrap().id() == 76);
+        assert!(coin.gid.unwrap().flipped_horizontally());
+        assert!(map.tile_for_gid(coin.gid.unwrap().id()).unwrap().id == 2);
+
+        let mut resources: HashMap<&str, &[u8]> = HashMap::new();
+        resources.insert("tilesets/dungeon.tsx", include_bytes!("../resources/test/tilesets/dungeon.tsx"));
+
+        match Map::parse_with_loader(contents.as_str(), &resources) {
+            Err(TmxError::Resource { path, .. }) => assert!(path == "templates/orc.tx"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_shapes() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("shapes.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let objects = &map.object_layers().next().unwrap().layer.objects;
+
+        assert!(objects[0].shape == ObjectShape::Rectangle);
+        assert!(objects[0].width == 128.0);
+
+        assert!(objects[1].shape == ObjectShape::Ellipse);
+        assert!(objects[1].height == 48.0);
+
+        assert!(objects[2].shape == ObjectShape::Point);
+        assert!(objects[2].x == 96.5);
+        assert!(objects[2].y == 160.0);
+
+        assert!(objects[3].shape == ObjectShape::Polygon(vec![(0.0, 0.0), (64.0, -32.0), (128.0, 0.0), (64.0, 32.5)]));
+        assert!(objects[4].shape == ObjectShape::Polyline(vec![(0.0, 0.0), (100.0, 0.0), (100.0, -50.0)]));
+
+        match &objects[5].shape {
+            ObjectShape::Text(text) => {
+                assert!(text.text == "Welcome & good luck");
+                assert!(text.fontfamily == "Serif");
+                assert!(text.pixelsize == 20);
+                assert!(text.wrap);
+                assert!(text.color == "#ff00ff00");
+                assert!(text.bold);
+                assert!(!text.italic);
+                assert!(!text.kerning);
+                assert!(text.halign == HorizontalAlignment::Center);
+                assert!(text.valign == VerticalAlignment::Bottom);
+            },
+            other => panic!("Unexpected shape {:?}", other),
+        }
+
+        match &objects[6].shape {
+            ObjectShape::Text(text) => {
+                assert!(text.text == "Hello");
+                assert!(text.fontfamily == "sans-serif");
+                assert!(text.pixelsize == 16);
+                assert!(!text.wrap);
+                assert!(text.color == "#000000");
+                assert!(text.kerning);
+                assert!(text.halign == HorizontalAlignment::Left);
+                assert!(text.valign == VerticalAlignment::Top);
+            },
+            other => panic!("Unexpected shape {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_imagelayers() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("imagelayers.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let imagelayers: Vec<&Imagelayer> = map.image_layers().map(|l| l.layer).collect();
+        assert!(imagelayers.len() == 3);
+
+        let sky = imagelayers[0];
+        assert!(sky.id == 1);
+        assert!(sky.name == "sky");
+        assert!(sky.visible);
+        assert!(sky.opacity == 1.0);
+        assert!(sky.repeatx);
+        assert!(!sky.repeaty);
+        assert!(sky.parallaxx == 0.25);
+        assert!(sky.parallaxy == 0.5);
+
+        let image = sky.image.as_ref().unwrap();
+        assert!(image.source == "backgroundMountain.png");
+        assert!(image.trans.as_ref().unwrap() == "ff00ff");
+        assert!(image.width == 512);
+        assert!(image.height == Some(256));
+
+        let towers = imagelayers[1];
+        assert!(towers.offsetx == 40);
+        assert!(towers.offsety == -12);
+        assert!(towers.opacity == 0.8);
+        assert!(!towers.visible);
+        assert!(towers.repeatx && towers.repeaty);
+        assert!(towers.parallaxx == 1.0);
+        assert!(towers.properties.get_bool("scroll") == Some(true));
+        assert!(towers.image.as_ref().unwrap().source == "backgroundTower.png");
+
+        let placeholder = imagelayers[2];
+        assert!(placeholder.image.is_none());
+    }
+
+    #[test]
+    fn test_read_groups() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("groups.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let groups: Vec<&Group> = map.layers.iter().filter_map(|l| match l { LayerKind::Group(g) => Some(g), _ => None }).collect();
+        assert!(groups.len() == 3);
+        assert!(groups.iter().map(|g| g.name.as_str()).collect::<Vec<&str>>() == vec!["background", "gameplay", "foreground"]);
+
+        let background = groups[0];
+        assert!(background.tintcolor == Some(Rgba { r: 255, g: 128, b: 128, a: 255 }));
+
+        let layers = background.effective_layers();
+        assert!(layers.iter().map(|(l, _)| l.name()).collect::<Vec<&str>>() == vec!["sky", "far"]);
+
+        let (sky, sky_values) = layers[0];
+        assert!(match sky { LayerKind::Image(image) => image.image.is_some(), _ => false });
+        assert!(sky_values == EffectiveValues { offsetx: 10, offsety: 0, opacity: 0.5, visible: true, tintcolor: Some(Rgba { r: 255, g: 128, b: 128, a: 255 }) });
+
+        let (far, far_values) = layers[1];
+        assert!(match far { LayerKind::Tile(layer) => layer.gid_at(1, 1) == Some(4), _ => false });
+        assert!(far_values.offsetx == 15);
+        assert!(far_values.offsety == 5);
+        assert!(far_values.opacity == 0.25);
+        assert!(far_values.tintcolor == Some(Rgba { r: 255, g: 128, b: 128, a: 128 }));
+
+        let gameplay = groups[1];
+        assert!(gameplay.properties.get_bool("physics") == Some(true));
+
+        let layers = gameplay.effective_layers();
+        assert!(layers.iter().map(|(l, _)| l.name()).collect::<Vec<&str>>() == vec!["spawns", "walls", "details", "decals", "empty"]);
+        assert!(layers.iter().map(|(l, _)| l.id()).collect::<Vec<i64>>() == vec![5, 6, 7, 8, 9]);
+        assert!(layers[0].1 == EffectiveValues::default());
+        assert!(layers[1].1 == EffectiveValues::default());
+
+        let (decals, decals_values) = layers[3];
+        assert!(decals.visible());
+        assert!(!decals_values.visible);
+        assert!(decals_values.opacity == 0.5);
+        assert!(decals_values.offsety == -4);
+        assert!(decals_values.tintcolor.is_none());
+
+        let foreground = groups[2];
+        let layers = foreground.effective_layers();
+        match layers[0].0 {
+            LayerKind::Object(objectgroup) => assert!(objectgroup.objects.len() == 1),
+            other => panic!("Unexpected layer {:?}", other),
+        }
+        assert!(layers[0].1.offsetx == -2);
+        assert!(layers[0].1.offsety == 3);
+    }
+
+    #[test]
+    fn test_layer_order() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("groups.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let names: Vec<(usize, &str)> = map.layers().map(|l| (l.z, l.layer.name())).collect();
+        assert!(names == vec![
+            (0, "background"), (1, "sky"), (2, "far"),
+            (3, "gameplay"), (4, "spawns"), (5, "walls"), (6, "details"), (7, "decals"), (8, "empty"),
+            (9, "foreground"), (10, "above"),
+        ]);
+
+        assert!(map.tile_layers().map(|l| (l.z, l.layer.name.as_str())).collect::<Vec<_>>() == vec![(2, "far"), (5, "walls")]);
+        assert!(map.object_layers().map(|l| l.z).collect::<Vec<usize>>() == vec![4, 10]);
+        assert!(map.image_layers().map(|l| l.z).collect::<Vec<usize>>() == vec![1, 7]);
+        assert!(map.group_layers().map(|l| l.z).collect::<Vec<usize>>() == vec![0, 3, 6, 8, 9]);
+
+        let decals = map.image_layers().last().unwrap();
+        assert!(!decals.values.visible);
+        assert!(decals.values.offsety == -4);
+    }
+
+    #[test]
+    fn test_rgba() {
+        assert!(Rgba::from_hex("#27b99a") == Some(Rgba { r: 0x27, g: 0xb9, b: 0x9a, a: 255 }));
+        assert!(Rgba::from_hex("80ff0000") == Some(Rgba { r: 255, g: 0, b: 0, a: 0x80 }));
+        assert!(Rgba::from_hex("#fff").is_none());
+        assert!(Rgba::from_hex("#gg0000").is_none());
+        assert!(Rgba::from_hex("#ff8080").unwrap().multiply(Rgba::from_hex("#8080ff").unwrap()) == Rgba { r: 128, g: 64, b: 128, a: 255 });
+    }
+
+    #[test]
+    fn test_error_invalid_points() {
+        let xml = format!("<map {}><objectgroup name=\"a\"><object id=\"1\" x=\"0\" y=\"0\"><polyline points=\"0,0 1,x\"/></object></objectgroup></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml) {
+            Err(TmxError::InvalidNumber { element, value, .. }) => {
+                assert!(element == "polyline");
+                assert!(value == "1,x");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn round_trip(map: &Map, loader: &dyn ResourceLoader) -> (String, Map) {
+        let mut output = Vec::new();
+        map.write_to(&mut output).unwrap();
+        let written = String::from_utf8(output).unwrap();
+
+        let read = Map::parse_with_loader(&written, loader)
+            .unwrap_or_else(|e| panic!("Unable to read the written map: {}\n{}", e, written));
+        (written, read)
+    }
+
+    #[test]
+    fn test_isometric_coordinates() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("isometric.tmx");
+
+        let map = Map::from_path(d).unwrap();
+        assert!(map.orientation == Orientation::Isometric);
+
+        // The top corner of the first tile is in the middle of the map's width.
+        assert!(map.tile_to_pixel(0, 0) == (768.0, 0.0));
+        assert!(map.tile_to_pixel(3, 1) == (832.0, 64.0));
+        assert!(map.tile_to_pixel(0, 24) == (0.0, 384.0));
+        assert!(map.tile_polygon(0, 0) == vec![(800.0, 0.0), (832.0, 16.0), (800.0, 32.0), (768.0, 16.0)]);
+
+        assert!(map.pixel_to_tile(800.0, 1.0) == (0, 0));
+        assert!(map.pixel_to_tile(800.0, -1.0) == (-1, -1));
+        assert!(map.pixel_to_tile(865.0, 80.0) == (3, 1));
+        assert!(map.pixel_to_tile(770.0, 16.0) == (0, 0));
+        assert!(map.pixel_to_tile(780.0, 26.0) == (0, 1));
+        assert!(map.pixel_to_tile(766.0, 16.0) == (-1, 1));
+
+        assert!(map.neighbours(3, 1) == vec![(3, 0), (4, 1), (3, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn test_staggered_coordinates() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("staggered.tmx");
+
+        let map = Map::from_path(d).unwrap();
+        assert!(map.staggeraxis == StaggerAxis::Y && map.staggerindex == StaggerIndex::Odd);
+
+        // Odd rows are shifted right by half a tile.
+        assert!(map.tile_to_pixel(0, 0) == (0.0, 0.0));
+        assert!(map.tile_to_pixel(1, 1) == (96.0, 16.0));
+        assert!(map.tile_to_pixel(2, 4) == (128.0, 64.0));
+        assert!(map.tile_polygon(0, 0) == vec![(32.0, 0.0), (64.0, 16.0), (32.0, 32.0), (0.0, 16.0)]);
+
+        assert!(map.pixel_to_tile(32.0, 16.0) == (0, 0));
+        assert!(map.pixel_to_tile(128.0, 32.0) == (1, 1));
+        assert!(map.pixel_to_tile(1.0, 1.0) == (-1, -1));
+        assert!(map.pixel_to_tile(63.0, 1.0) == (0, -1));
+
+        assert!(map.neighbours(1, 1) == vec![(2, 0), (2, 2), (1, 2), (1, 0)]);
+        assert!(map.neighbours(1, 2) == vec![(1, 1), (1, 3), (0, 3), (0, 1)]);
+    }
+
+    #[test]
+    fn test_hexagonal_coordinates() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("hexagonal.tmx");
+
+        let map = Map::from_path(d).unwrap();
+        assert!(map.hexsidelength == 6);
+
+        assert!(map.tile_to_pixel(0, 0) == (0.0, 0.0));
+        assert!(map.tile_to_pixel(0, 1) == (7.0, 9.0));
+        assert!(map.tile_to_pixel(2, 3) == (35.0, 27.0));
+        assert!(map.tile_polygon(0, 0) == vec![(7.0, 0.0), (14.0, 3.0), (14.0, 9.0), (7.0, 12.0), (0.0, 9.0), (0.0, 3.0)]);
+
+        assert!(map.pixel_to_tile(7.0, 6.0) == (0, 0));
+        assert!(map.pixel_to_tile(14.0, 12.0) == (0, 1));
+        assert!(map.pixel_to_tile(42.0, 33.0) == (2, 3));
+
+        assert!(map.neighbours(2, 3) == vec![(3, 2), (3, 3), (3, 4), (2, 4), (1, 3), (2, 2)]);
+        assert!(map.neighbours(2, 2) == vec![(2, 1), (3, 2), (2, 3), (1, 3), (1, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn test_coordinates_round_trip() {
+
+        let layouts = [
+            r#"orientation="orthogonal" tilewidth="32" tileheight="24""#,
+            r#"orientation="isometric" tilewidth="64" tileheight="32""#,
+            r#"orientation="staggered" tilewidth="64" tileheight="32" staggeraxis="y" staggerindex="odd""#,
+            r#"orientation="staggered" tilewidth="64" tileheight="32" staggeraxis="y" staggerindex="even""#,
+            r#"orientation="staggered" tilewidth="32" tileheight="48" staggeraxis="x" staggerindex="odd""#,
+            r#"orientation="staggered" tilewidth="32" tileheight="48" staggeraxis="x" staggerindex="even""#,
+            r#"orientation="hexagonal" tilewidth="14" tileheight="12" hexsidelength="6" staggeraxis="y" staggerindex="odd""#,
+            r#"orientation="hexagonal" tilewidth="14" tileheight="12" hexsidelength="6" staggeraxis="y" staggerindex="even""#,
+            r#"orientation="hexagonal" tilewidth="30" tileheight="26" hexsidelength="14" staggeraxis="x" staggerindex="odd""#,
+            r#"orientation="hexagonal" tilewidth="30" tileheight="26" hexsidelength="14" staggeraxis="x" staggerindex="even""#,
+        ];
+
+        for layout in layouts.iter() {
+            let xml = format!(r#"<map version="1.10" {} width="6" height="6"></map>"#, layout);
+            let map = Map::parse(&xml).unwrap();
+
+            for y in -3..6 {
+                for x in -3..6 {
+                    let polygon = map.tile_polygon(x, y);
+                    let count = polygon.len() as f32;
+                    let center = polygon.iter().fold((0.0, 0.0), |(cx, cy), (px, py)| (cx + px / count, cy + py / count));
+                    assert!(map.pixel_to_tile(center.0, center.1) == (x, y), "{} {:?} {},{}", layout, center, x, y);
+
+                    for (nx, ny) in map.neighbours(x, y) {
+                        assert!(map.neighbours(nx, ny).contains(&(x, y)), "{} {},{} {},{}", layout, x, y, nx, ny);
+                        let shared = map.tile_polygon(nx, ny).iter().filter(|point| polygon.contains(point)).count();
+                        assert!(shared == 2, "{} {},{} {},{}", layout, x, y, nx, ny);
+                    }
+                }
+            }
+
+            // The cells at the limits of i32 don't overflow.
+            for &(x, y) in &[(i32::MAX, i32::MIN), (i32::MIN, i32::MAX)] {
+                assert!(map.tile_polygon(x, y).len() >= 4);
+                assert!(map.neighbours(x, y).iter().all(|(nx, ny)| (nx - x).abs() <= 1 && (ny - y).abs() <= 1));
+            }
+            map.pixel_to_tile(f32::MAX, -f32::MAX);
+        }
+    }
+
+    /// A map whose single layer is filled with the first tile, except for the cells given in `cells`.
+    fn render_map(layout: &str, width: i32, height: i32, cells: &[(usize, Gid)]) -> Map {
+        let mut data = vec![Gid(1); (width * height) as usize];
+        for (i, gid) in cells {
+            data[*i] = *gid;
+        }
+        let data: Vec<String> = data.iter().map(|gid| gid.to_string()).collect();
+
+        Map::parse(&format!(r#"<map version="1.10" {} width="{}" height="{}">
+             <tileset firstgid="1" name="tiles" tilewidth="32" tileheight="32" tilecount="4" columns="2">
+              <image source="tiles.png" width="64" height="64"/>
+             </tileset>
+             <layer id="1" name="a" width="{}" height="{}"><data encoding="csv">{}</data></layer>
+            </map>"#, layout, width, height, width, height, data.join(","))).unwrap()
+    }
+
+    #[test]
+    fn test_render_order() {
+
+        let cells = [(1, Gid::EMPTY), (3, Gid(2 | Gid::FLIPPED_HORIZONTALLY | Gid::FLIPPED_DIAGONALLY))];
+        let order = |renderorder: &str| -> Vec<(i32, i32)> {
+            let layout = format!(r#"orientation="orthogonal" renderorder="{}" tilewidth="32" tileheight="32""#, renderorder);
+            let map = render_map(&layout, 3, 2, &cells);
+            let layer = map.tile_layers().next().unwrap().layer;
+            map.render_cells(layer, None).map(|(x, y, _)| (x, y)).collect()
+        };
+
+        assert!(order("right-down") == vec![(0, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+        assert!(order("right-up") == vec![(0, 1), (1, 1), (2, 1), (0, 0), (2, 0)]);
+        assert!(order("left-down") == vec![(2, 0), (0, 0), (2, 1), (1, 1), (0, 1)]);
+        assert!(order("left-up") == vec![(2, 1), (1, 1), (0, 1), (2, 0), (0, 0)]);
+
+        let map = render_map(r#"orientation="orthogonal" tilewidth="32" tileheight="32""#, 3, 2, &cells);
+        let layer = map.tile_layers().next().unwrap().layer;
+        let (_, _, flipped) = map.render_cells(layer, None).find(|(x, y, _)| (*x, *y) == (0, 1)).unwrap();
+        assert!(flipped.id() == 2);
+        assert!(flipped.flip_flags() == FlipFlags { horizontally: true, vertically: false, diagonally: true, rotated_hexagonal_120: false });
+
+        // Diagonals of the grid are the rows of isometric maps, shifted columns rows of their own in staggered ones.
+        let map = render_map(r#"orientation="isometric" tilewidth="64" tileheight="32""#, 3, 3, &[]);
+        let layer = map.tile_layers().next().unwrap().layer;
+        let cells: Vec<(i32, i32)> = map.render_cells(layer, None).map(|(x, y, _)| (x, y)).collect();
+        assert!(cells == vec![(0, 0), (0, 1), (1, 0), (0, 2), (1, 1), (2, 0), (1, 2), (2, 1), (2, 2)]);
+
+        let map = render_map(r#"orientation="staggered" tilewidth="64" tileheight="32" staggeraxis="x" staggerindex="odd""#, 4, 2, &[]);
+        let layer = map.tile_layers().next().unwrap().layer;
+        let cells: Vec<(i32, i32)> = map.render_cells(layer, None).map(|(x, y, _)| (x, y)).collect();
+        assert!(cells == vec![(0, 0), (2, 0), (1, 0), (3, 0), (0, 1), (2, 1), (1, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_render_viewport() {
+
+        let map = render_map(r#"orientation="orthogonal" tilewidth="32" tileheight="32""#, 10, 10, &[]);
+        let layer = map.tile_layers().next().unwrap().layer;
+        let viewport = Rect { x: 40, y: 40, width: 64, height: 32 };
+        let cells: Vec<(i32, i32)> = map.render_cells(layer, Some(viewport)).map(|(x, y, _)| (x, y)).collect();
+        assert!(cells == vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]);
+
+        let outside = Rect { x: -100, y: 0, width: 50, height: 50 };
+        assert!(map.render_cells(layer, Some(outside)).next().is_none());
+
+        let layouts = [
+            r#"orientation="orthogonal" tilewidth="32" tileheight="32""#,
+            r#"orientation="isometric" tilewidth="64" tileheight="32""#,
+            r#"orientation="staggered" tilewidth="64" tileheight="32" staggeraxis="y" staggerindex="even""#,
+            r#"orientation="hexagonal" tilewidth="30" tileheight="26" hexsidelength="14" staggeraxis="x" staggerindex="odd""#,
+        ];
+        let viewports = [
+            Rect { x: 0, y: 0, width: 100, height: 60 },
+            Rect { x: 150, y: 90, width: 33, height: 47 },
+            Rect { x: -20, y: 200, width: 300, height: 10 },
+            Rect { x: 310, y: -5, width: 1, height: 1 },
+        ];
+
+        for layout in layouts.iter() {
+            // Tiles twice as high as the grid reach into the cells above them.
+            let mut map = render_map(layout, 12, 12, &[]);
+            map.tilesets[0].tileheight = map.tileheight * 2;
+            let layer = map.tile_layers().next().unwrap().layer;
+            let all: Vec<(i32, i32)> = map.render_cells(layer, None).map(|(x, y, _)| (x, y)).collect();
+
+            for viewport in viewports.iter() {
+                let expected: Vec<(i32, i32)> = all.iter().copied().filter(|(x, y)| {
+                    let (left, top) = map.tile_to_pixel(*x, *y);
+                    let (left, top) = (left.floor() as i32, top.floor() as i32 - map.tileheight);
+                    left < viewport.x + viewport.width && viewport.x < left + map.tilewidth
+                        && top < viewport.y + viewport.height && viewport.y < top + map.tileheight * 2
+                }).collect();
+
+                let cells: Vec<(i32, i32)> = map.render_cells(layer, Some(*viewport)).map(|(x, y, _)| (x, y)).collect();
+                assert!(cells == expected, "{} {:?}\n{:?}\n{:?}", layout, viewport, cells, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gid() {
+
+        // Tiled sets the fourth bit for tiles rotated by 120 degrees in hexagonal maps.
+        let rotated = Gid(Gid::ROTATED_HEXAGONAL_120 | Gid::FLIPPED_HORIZONTALLY | 3);
+        assert!(rotated.id() == 3);
+        assert!(rotated.flip_flags() == FlipFlags { horizontally: true, vertically: false, diagonally: false, rotated_hexagonal_120: true });
+        assert!(Gid::new(3, rotated.flip_flags()) == Some(rotated));
+        assert!(rotated.with_id(7) == Some(Gid(Gid::ROTATED_HEXAGONAL_120 | Gid::FLIPPED_HORIZONTALLY | 7)));
+
+        // Ids reaching into the flag bits can't be stored.
+        assert!(Gid::new(Gid::MAX_ID, FlipFlags::default()).unwrap().id() == Gid::MAX_ID);
+        assert!(Gid::new(Gid::MAX_ID + 1, FlipFlags::default()).is_none());
+        assert!(Gid::new(-1, FlipFlags::default()).is_none());
+        assert!(rotated.with_id(1 << 32).is_none());
+
+        let map = render_map(r#"orientation="hexagonal" tilewidth="30" tileheight="26" hexsidelength="14""#, 2, 1, &[(1, rotated)]);
+        let layer = map.tile_layers().next().unwrap().layer;
+        assert!(layer.gid_at(1, 0) == Some(3));
+        assert!(map.tile_for_gid(rotated.0 as i64).unwrap().id == 2);
+
+        let close = |(ax, ay): (f32, f32), (bx, by): (f32, f32)| (ax - bx).abs() < 0.001 && (ay - by).abs() < 0.001;
+
+        // A diagonal and a horizontal flip rotate the tile by 90 degrees clockwise.
+        let quarter = Gid(Gid::FLIPPED_DIAGONALLY | Gid::FLIPPED_HORIZONTALLY | 1).transform();
+        assert!(close(quarter.apply((1.0, 0.0)), (0.0, 1.0)));
+        assert!(close(quarter.apply((0.0, 1.0)), (-1.0, 0.0)));
+
+        let turned = Gid(Gid::ROTATED_HEXAGONAL_120 | 1).transform();
+        assert!(close(turned.apply((1.0, 0.0)), (-0.5, 0.866)));
+
+        // Within the tile the top left corner of a vertically flipped image ends up at the bottom left.
+        let (flipped, width, height) = Gid(Gid::FLIPPED_VERTICALLY | 1).transform_within(32.0, 16.0);
+        assert!((width, height) == (32.0, 16.0));
+        assert!(close(flipped.apply((0.0, 0.0)), (0.0, 16.0)));
+
+        let (quarter, width, height) = Gid(Gid::FLIPPED_DIAGONALLY | Gid::FLIPPED_HORIZONTALLY | 1).transform_within(32.0, 16.0);
+        assert!((width, height) == (16.0, 32.0));
+        assert!(close(quarter.apply((0.0, 0.0)), (16.0, 0.0)));
+        assert!(quarter.to_matrix()[2] == [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_read_wangsets() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("wangsets.tmx");
+
+        let contents = fs::read_to_string(d)
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let tileset = &map.tilesets[0];
+        assert!(tileset.wangsets.len() == 3);
+
+        let ground = tileset.wangset("Ground").unwrap();
+        assert!(ground.wangsettype == WangSetType::Corner);
+        assert!(ground.tile.is_none());
+        assert!(ground.colors.len() == 2);
+        assert!(ground.colors[1].name == "dirt");
+        assert!(ground.colors[1].color == Rgba { r: 0x80, g: 0x40, b: 0, a: 0xff });
+        assert!(ground.colors[1].tile == Some(15));
+        assert!(ground.tiles.len() == 17);
+        let wangid = ground.wangid(6).unwrap();
+        assert!(wangid.color(WangId::BOTTOM_RIGHT) == 2 && wangid.color(WangId::BOTTOM_LEFT) == 2);
+        assert!(wangid.color(WangId::TOP_LEFT) == 1 && wangid.color(WangId::TOP) == 0);
+
+        let roads = tileset.wangset("Roads").unwrap();
+        assert!(roads.wangsettype == WangSetType::Edge);
+        assert!(roads.tile == Some(22));
+        assert!(roads.colors[0].tile.is_none());
+
+        let legacy = tileset.wangset("Legacy").unwrap();
+        assert!(legacy.wangsettype == WangSetType::Mixed);
+        assert!(legacy.colors[0].name == "grass");
+        assert!(legacy.wangid(0) == Some(WangId([0, 1, 0, 1, 0, 1, 0, 1])));
+    }
+
+    #[test]
+    fn test_autotile() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("wangsets.tmx");
+
+        let contents = fs::read_to_string(d)
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        let tileset = &map.tilesets[0];
+        let ground = tileset.wangset("Ground").unwrap();
+
+        let colors = [
+            1, 1, 1, 1,
+            1, 2, 1, 1,
+            1, 1, 1, 1,
+            1, 1, 1, 1,
+        ];
+        let gids = tileset.autotile(ground, &colors, 4, || 0.0);
+        assert!(gids == map.tile_layers().next().unwrap().layer.data);
+
+        // Tile 16 is another all grass tile, picked a fifth of the time because of its probability.
+        let gids = tileset.autotile(ground, &colors, 4, || 0.9);
+        assert!(gids[3] == Gid(17) && gids[15] == Gid(17) && gids[5] == Gid(16));
+
+        let roads = tileset.wangset("Roads").unwrap();
+        let colors = [
+            0, 1, 0,
+            1, 1, 1,
+            0, 0, 0,
+        ];
+        let gids = tileset.autotile(roads, &colors, 3, || 0.0);
+        assert!(gids.iter().map(|gid| gid.id()).collect::<Vec<i64>>() == vec![0, 22, 0, 21, 24, 21, 0, 0, 0]);
+    }
 
-        let objectgroup_parallax = objectgroups.get(0).unwrap();
-        let object_flippedcloud:&Object = objectgroup_parallax.objects.get(2).as_ref().unwrap();
+    #[test]
+    #[cfg(all(feature = "json", feature = "zlib"))]
+    fn test_read_json() {
 
-        assert!(object_flippedcloud.id == 91);
-        assert!(object_flippedcloud.gid.unwrap() == 7);
-        assert!(object_flippedcloud.x == 373.939);
-        assert!(object_flippedcloud.y == 627.121);
-        assert!(object_flippedcloud.width == 384.0);
-        assert!(!object_flippedcloud.flipped_vertically);
-        assert!(!object_flippedcloud.flipped_diagonally);
-        assert!(object_flippedcloud.flipped_horizontally);
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        let loader = FileSystemLoader::new(&d);
+
+        let xml = fs::read_to_string(d.join("formats.tmx"))
+            .expect("Something went wrong reading the file");
+        let json = fs::read_to_string(d.join("formats.tmj"))
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse_json_with_loader(json.as_str(), &loader).unwrap();
+        assert!(map == Map::parse_with_loader(xml.as_str(), &loader).unwrap());
+        assert!(map == Map::parse_any_with_loader(json.as_str(), &loader).unwrap());
+        assert!(map == Map::parse_any_with_loader(xml.as_str(), &loader).unwrap());
+
+        let terrain = &map.tilesets[0];
+        assert!(terrain.image.as_ref().unwrap().trans == Some("ff00ff".to_string()));
+        assert!(terrain.tile(5).unwrap().terrain == Some([Some(0), Some(0), None, Some(1)]));
+        assert!(terrain.properties.get_class("loot").unwrap().get_int("gold") == Some(3));
+        assert!(terrain.terraintypes[1].name == "water");
+        assert!(terrain.terraintypes[1].properties.get_bool("swim") == Some(true));
+
+        let shore = terrain.wangset("Shore").unwrap();
+        assert!(shore.wangsettype == WangSetType::Corner);
+        assert!(shore.class == Some("Coast".to_string()));
+        assert!(shore.colors[1].probability == 0.5);
+        assert!(shore.colors[1].properties.get_bool("deep") == Some(false));
+        assert!(shore.wangid(5) == Some(WangId([0, 1, 0, 2, 0, 1, 0, 1])));
+
+        let props = &map.tilesets[1];
+        assert!(props.source == Some("tilesets/props.tsj".to_string()));
+        assert!(props.tile(1).unwrap().tile_type == Some("Box".to_string()));
+
+        let details = map.tile_layers().nth(1).unwrap().layer;
+        assert!(details.encoding == DataEncoding::Base64);
+        assert!(details.compression == Some(Compression::Zlib));
+        assert!(details.raw_gid_at(3, 2) == Some(Gid(2147483652)));
+
+        let objects = &map.object_layers().next().unwrap().layer.objects;
+        assert!(objects[5].name == Some("barrel".to_string()));
+        assert!(objects[5].gid == Some(Gid(9)));
+        assert!(objects[5].properties.get_bool("breakable") == Some(true));
+        assert!(objects[6].gid.map(Gid::id) == Some(9) && objects[6].gid.unwrap().flipped_horizontally());
     }
 
     #[test]
-    fn test_read_desert() {
+    #[cfg(all(feature = "zlib", feature = "gzip", feature = "zstd"))]
+    fn test_read_from_reader() {
 
         let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         d.push("resources/test");
-        d.push("desert.tmx");
+        d.push("encodings.tmx");
 
         let contents = fs::read_to_string(d.as_os_str())
             .expect("Something went wrong reading the file");
 
-        let map = Map::new(contents.as_str());
+        // A tiny buffer splits the tile data across many reads.
+        let file = BufReader::with_capacity(16, File::open(&d).unwrap());
 
-        assert!(map.version == "1.0");
-        assert!(map.orientation == Orientation::Orthogonal);
-        assert!(map.renderorder == Renderorder::RightDown);
-        assert!(map.width == 40);
-        assert!(map.height == 40);
-        assert!(map.tilewidth == 32);
-        assert!(map.tileheight == 32);
+        assert!(Map::from_reader(file).unwrap() == Map::parse(contents.as_str()).unwrap());
+    }
+
+    #[test]
+    fn test_read_from_path() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+
+        let contents = fs::read_to_string(d.join("external.tmx"))
+            .expect("Something went wrong reading the file");
+        let map = Map::from_path(d.join("external.tmx")).unwrap();
+
+        assert!(map == Map::parse_with_loader(contents.as_str(), &FileSystemLoader::new(&d)).unwrap());
+        test_external_tilesets(&map);
+
+        match Map::from_path(d.join("missing.tmx")) {
+            Err(TmxError::Resource { path, .. }) => assert!(path.ends_with("missing.tmx")),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "zlib"))]
+    fn test_read_json_from_path() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+
+        assert!(Map::from_path(d.join("formats.tmj")).unwrap() == Map::from_path(d.join("formats.tmx")).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "world")]
+    fn test_read_world() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/worlds");
+
+        let world = World::from_path(d.join("dungeon.world")).unwrap();
+        assert!(!world.onlyshowadjacentmaps);
+        assert!(world.patterns[0].multiplierx == 320 && world.patterns[0].mapheight == 240);
+
+        let filenames: Vec<&str> = world.maps.iter().map(|map| map.filename.as_str()).collect();
+        assert!(filenames == vec!["rooms/hall.tmx", "../external.tmx", "room-0-0.tmx", "room-1-0.tmx"]);
+        assert!(world.maps[3].rect() == Rect { x: 320, y: 0, width: 320, height: 240 });
+
+        let visible = |x, y, width, height| -> Vec<&str> {
+            world.intersecting(Rect { x, y, width, height }).map(|map| map.filename.as_str()).collect()
+        };
+        assert!(visible(0, 0, 320, 240) == vec!["room-0-0.tmx"]);
+        assert!(visible(300, -20, 40, 40) == vec!["rooms/hall.tmx", "room-0-0.tmx", "room-1-0.tmx"]);
+        assert!(visible(-60, 10, 20, 20) == vec!["../external.tmx"]);
+        assert!(visible(0, 240, 640, 100).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "world")]
+    fn test_load_world_maps() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/worlds");
+        let loader = FileSystemLoader::new(&d);
+
+        let contents = fs::read_to_string(d.join("dungeon.world"))
+            .expect("Something went wrong reading the file");
+
+        // Without a directory to list, the patterns are matched against the names given to them.
+        let mut world = World::parse(contents.as_str()).unwrap();
+        assert!(world.maps.len() == 2);
+        world.add_matching_maps(vec!["room-1-0.tmx", "room-a-b.tmx", "rooms/hall.tmx", "room-1-0.tmj.bak"]);
+        assert!(world.maps.len() == 3);
+        assert!(world.maps[2].x == 320);
+
+        // Numbers placing a map beyond the range of i32 don't match, areas at its edge overlap nothing.
+        world.add_matching_maps(vec!["room-99999999-0.tmx", "room-0-2147483647.tmx"]);
+        assert!(world.maps.len() == 3);
+        assert!(world.intersecting(Rect { x: i32::MAX - 10, y: i32::MAX - 10, width: i32::MAX, height: i32::MAX }).count() == 0);
+
+        for map in &world.maps {
+            let loaded = world.load_map(map, &loader).unwrap();
+            assert!(loaded.tilesets[0].name == "dungeon");
+        }
+
+        let hall = world.load_map(&world.maps[0], &loader).unwrap();
+        assert!(hall.width * hall.tilewidth == world.maps[0].width);
+        assert!(hall.tilesets[0].source == Some("../../tilesets/dungeon.tsx".to_string()));
+    }
+
+    #[test]
+    #[cfg(feature = "world")]
+    fn test_error_world() {
+        match World::parse(r#"{ "patterns": [{ "regexp": "room-(\\d+", "multiplierX": 16, "multiplierY": 16 }] }"#) {
+            Err(TmxError::InvalidWorld(_)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        match World::parse(r#"{ "maps": [{ "fileName": "a.tmx", "x": "left" }] }"#) {
+            Err(TmxError::InvalidWorld(message)) => assert!(message.contains("'x'")),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let world = World::parse(r#"{ "maps": [{ "fileName": "missing.tmx", "width": 16, "height": 16 }] }"#).unwrap();
+        match world.load_map(&world.maps[0], &HashMap::<&str, &[u8]>::new()) {
+            Err(TmxError::Resource { path, .. }) => assert!(path == "missing.tmx"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_format_detect() {
+        assert!(Format::detect(include_str!("../resources/test/formats.tmx")) == Format::Xml);
+        assert!(Format::detect(include_str!("../resources/test/formats.tmj")) == Format::Json);
+        assert!(Format::detect("\u{feff}\n  {}") == Format::Json);
+        assert!(Format::detect("") == Format::Xml);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_read_json_class_members() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        let project = Project::load("enemies.tiled-project", &FileSystemLoader::new(d)).unwrap();
+
+        let json = r##"{ "version": "1.10", "orientation": "orthogonal", "width": 2, "height": 1, "tilewidth": 32,
+            "tileheight": 32,
+            "properties": [{ "name": "boss", "type": "class", "propertytype": "Enemy",
+                "value": { "speed": 2, "loot": { "item": "items/axe.png" } } }] }"##;
+
+        let mut map = Map::parse_json(json).unwrap();
+        assert!(map.properties.get_class("boss").unwrap().get_int("speed") == Some(2));
+
+        map.apply_project(&project).unwrap();
+
+        let boss = map.properties.get_class("boss").unwrap();
+        assert!(boss.get_float("speed") == Some(2.0));
+        assert!(boss.get_class("loot").unwrap().get_int("gold") == Some(20));
+        assert!(boss.get_class("loot").unwrap().get_file("item") == Some(PathBuf::from("items/axe.png").as_path()));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_read_large_json_from_path() {
+
+        let (width, height) = (1000, 1000);
+        let data: Vec<String> = (0..width * height).map(|i| (i % 64 + 1).to_string()).collect();
+        let json = format!("\u{feff}{{ \"type\": \"map\", \"version\": \"1.10\", \"orientation\": \"orthogonal\", \
+            \"width\": {w}, \"height\": {h}, \"tilewidth\": 16, \"tileheight\": 16, \"tilesets\": [], \
+            \"layers\": [{{ \"type\": \"tilelayer\", \"id\": 1, \"name\": \"Ground\", \"width\": {w}, \"height\": {h}, \
+            \"data\": [{data}] }}] }}", w = width, h = height, data = data.join(","));
+
+        let path = std::env::temp_dir().join(format!("tmx_reader_large_{}.tmj", std::process::id()));
+        fs::write(&path, json).unwrap();
+        let map = Map::from_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        let map = map.unwrap();
+        let layer = map.tile_layers().next().unwrap().layer;
+        assert!(layer.data.len() == (width * height) as usize);
+        assert!(layer.gid_at(width - 1, height - 1) == Some(i64::from((width * height - 1) % 64 + 1)));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_read_json_grouped_chunks() {
+        let json = r##"{ "version": "1.10", "orientation": "orthogonal", "width": 2, "height": 2, "tilewidth": 32,
+            "tileheight": 32, "infinite": true, "layers": [{ "type": "group", "name": "g", "layers": [
+                { "type": "tilelayer", "name": "a", "width": 2, "height": 2, "chunks": [
+                    { "x": -2, "y": 0, "width": 2, "height": 2, "data": "AQAAAAIAAAADAAAABAAAAA==" },
+                    { "x": 0, "y": 0, "width": 2, "height": 2, "data": [5, 6, 7, 8] }
+                ], "encoding": "base64" }] }] }"##;
+
+        let map = Map::parse_json(json).unwrap();
+        let layer = map.tile_layers().next().unwrap().layer;
+        assert!(layer.gid_at(-1, 1) == Some(4));
+        assert!(layer.gid_at(0, 1) == Some(7));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_read_json_without_background() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("plain.tmj");
+
+        let map = Map::from_path(&d).unwrap();
+        assert!(map.backgroundcolor.is_none());
+        assert!(map.nextobjectid == 1);
+        assert!(map.tilesets[0].tilecount == 4);
+
+        let layer = map.tile_layers().next().unwrap().layer;
+        assert!(layer.gid_at(1, 0) == Some(2) && layer.gid_at(1, 1) == Some(3));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_error_json() {
+        match Map::parse_json("{ \"version\": ") {
+            Err(TmxError::InvalidJson(_)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        match Map::parse_any(r#"{ "version": "1.10", "orientation": "orthogonal" }"#) {
+            Err(TmxError::MissingAttribute { element, attribute }) => {
+                assert!(element == "map");
+                assert!(attribute == "width");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let json = r##"{ "version": "1.10", "orientation": "orthogonal", "width": 2, "height": 1, "tilewidth": 32,
+            "tileheight": 32,
+            "layers": [{ "type": "tilelayer", "name": "a", "width": 2, "height": 1, "data": [1, "x"] }] }"##;
+
+        match Map::parse_json(json) {
+            Err(TmxError::InvalidTileData(_)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "json"))]
+    fn test_error_json_disabled() {
+        match Map::parse_any(include_str!("../resources/test/formats.tmj")) {
+            Err(TmxError::InvalidJson(_)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "zlib", feature = "gzip", feature = "zstd", feature = "json"))]
+    fn test_write_round_trip() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        let loader = FileSystemLoader::new(&d);
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&d).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map(|e| e == "tmx").unwrap_or(false))
+            .collect();
+        files.sort();
+        assert!(files.len() > 10);
+
+        for file in files {
+            let contents = fs::read_to_string(&file)
+                .expect("Something went wrong reading the file");
+
+            let map = Map::parse_with_loader(contents.as_str(), &loader).unwrap();
+            let (written, read) = round_trip(&map, &loader);
+            assert!(read == map, "{:?}\n{}", file, written);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "zlib", feature = "gzip", feature = "zstd"))]
+    fn test_write_encodings() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("infinite.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let mut map = Map::parse(contents.as_str()).unwrap();
+
+        let encodings = [
+            (DataEncoding::Xml, None, "<tile gid=\"8\"/>"),
+            (DataEncoding::Csv, None, "encoding=\"csv\""),
+            (DataEncoding::Base64, None, "encoding=\"base64\">"),
+            (DataEncoding::Base64, Some(Compression::Zlib), "compression=\"zlib\""),
+            (DataEncoding::Base64, Some(Compression::Gzip), "compression=\"gzip\""),
+            (DataEncoding::Base64, Some(Compression::Zstd), "compression=\"zstd\""),
+        ];
+
+        for (encoding, compression, expected) in encodings.iter() {
+            for layer in map.layers.iter_mut() {
+                if let LayerKind::Tile(layer) = layer {
+                    layer.encoding = *encoding;
+                    layer.compression = *compression;
+                }
+            }
+
+            let (written, read) = round_trip(&map, &NoLoader);
+            assert!(written.contains(expected), "{}", written);
+            assert!(read == map, "{}", written);
+        }
+    }
+
+    #[test]
+    fn test_write_properties() {
+        let xml = format!(r##"<map {}>
+             <properties>
+              <property name="text" value="a &amp; &quot;b&quot;"/>
+              <property name="lines">first
+second</property>
+              <property name="indented">{}</property>
+              <property name="color" type="color" value="#80ff0000"/>
+              <property name="unset" type="color" value=""/>
+              <property name="file" type="file" value="../a b.png"/>
+              <property name="boss" type="class" propertytype="Enemy">
+               <properties>
+                <property name="hp" type="int" value="-5"/>
+                <property name="speed" type="float" value="0.1"/>
+               </properties>
+              </property>
+              <property name="empty" type="class" propertytype="Loot"/>
+             </properties>
+             <layer name="a" width="2" height="1"><data encoding="csv">1,2</data></layer>
+            </map>"##, MAP_ATTRIBUTES, "\n  indented\nlast line  \n");
+
+        let map = Map::parse(&xml).unwrap();
+        let (written, read) = round_trip(&map, &NoLoader);
+
+        assert!(read == map, "{}", written);
+        assert!(read.properties.get_string("lines") == Some("first\nsecond"));
+        // The whitespace around the lines is part of the value.
+        assert!(read.properties.get_string("indented") == Some("\n  indented\nlast line  \n"));
+        assert!(read.properties.get_color("color") == Some(Rgba { r: 255, g: 0, b: 0, a: 128 }));
+        assert!(written.contains("value=\"#80ff0000\""));
+    }
+
+    const MAP_ATTRIBUTES: &str = r##"version="1.0" orientation="orthogonal" width="2" height="1" tilewidth="32" tileheight="32""##;
+
+    #[test]
+    fn test_read_property_types() {
+        let xml = format!(r##"<map {}>
+            <properties>
+                <property name="name" value="level 1"/>
+                <property name="lives" type="int" value="-3"/>
+                <property name="gravity" type="float" value="9.81"/>
+                <property name="dark" type="bool" value="true"/>
+                <property name="fog" type="color" value="#80ff0000"/>
+                <property name="unset" type="color" value=""/>
+                <property name="music" type="file" value="music/level1.ogg"/>
+                <property name="spawn" type="object" value="12"/>
+                <property name="intro">first line
+second line</property>
+            </properties>
+        </map>"##, MAP_ATTRIBUTES);
+
+        let properties = Map::parse(&xml).unwrap().properties;
+        assert!(properties.len() == 9);
+        assert!(properties.get_string("name") == Some("level 1"));
+        assert!(properties.get_int("lives") == Some(-3));
+        assert!(properties.get_float("gravity") == Some(9.81));
+        assert!(properties.get_bool("dark") == Some(true));
+        assert!(properties.get_color("fog") == Some(Rgba { r: 255, g: 0, b: 0, a: 0x80 }));
+        assert!(properties.get_color("unset") == Some(Rgba { r: 0, g: 0, b: 0, a: 0 }));
+        assert!(properties.get_file("music") == Some(PathBuf::from("music/level1.ogg").as_path()));
+        assert!(properties.get_object("spawn") == Some(12));
+        assert!(properties.get_string("intro") == Some("first line\nsecond line"));
+        assert!(properties.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>()
+            == vec!["name", "lives", "gravity", "dark", "fog", "unset", "music", "spawn", "intro"]);
+    }
+
+    #[test]
+    fn test_read_class_properties() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("enemies.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let map = Map::parse(contents.as_str()).unwrap();
+        assert!(map.properties.get_class("boss").unwrap().is_empty());
+        assert!(map.properties.iter().next().unwrap().propertytype == Some("Enemy".to_string()));
+
+        let objects = &map.object_layers().next().unwrap().layer.objects;
+
+        let orc = objects[0].properties.get_class("enemy").unwrap();
+        assert!(orc.len() == 1);
+        assert!(orc.get_int("hp") == Some(30));
+
+        let lich = objects[1].properties.get_class("enemy").unwrap();
+        assert!(lich.get_string("faction") == Some("Undead"));
+        assert!(lich.get_int("immunities") == Some(5));
+        assert!(lich.get_class("loot").unwrap().get_file("item") == Some(PathBuf::from("items/staff.png").as_path()));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_apply_project() {
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test");
+        d.push("enemies.tmx");
+
+        let contents = fs::read_to_string(d.as_os_str())
+            .expect("Something went wrong reading the file");
+
+        let project = Project::load("enemies.tiled-project", &FileSystemLoader::new(d.parent().unwrap())).unwrap();
+        assert!(project.propertytypes.iter().map(CustomType::name).collect::<Vec<&str>>() == vec!["Faction", "Element", "Loot", "Enemy"]);
+
+        let mut map = Map::parse(contents.as_str()).unwrap();
+        map.apply_project(&project).unwrap();
+
+        let boss = map.properties.get_class("boss").unwrap();
+        assert!(boss.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>() == vec!["hp", "speed", "faction", "immunities", "loot"]);
+        assert!(boss.get_int("hp") == Some(10));
+        assert!(boss.get_float("speed") == Some(1.5));
+        assert!(boss.get_enum("faction") == Some(&["Orcs".to_string()][..]));
+        assert!(boss.get_enum("immunities") == Some(&[][..]));
+        assert!(boss.get_class("loot").unwrap().get_int("gold") == Some(20));
+        assert!(map.properties.get_string("weather") == Some("rain"));
+
+        let objects = &map.object_layers().next().unwrap().layer.objects;
+
+        let orc = objects[0].properties.get_class("enemy").unwrap();
+        assert!(orc.get_int("hp") == Some(30));
+        assert!(orc.get_float("speed") == Some(1.5));
+
+        let lich = objects[1].properties.get_class("enemy").unwrap();
+        assert!(lich.get_int("hp") == Some(10));
+        assert!(lich.get_enum("faction") == Some(&["Undead".to_string()][..]));
+        assert!(lich.get_enum("immunities") == Some(&["Fire".to_string(), "Poison".to_string()][..]));
+
+        let loot = lich.get_class("loot").unwrap();
+        assert!(loot.get_int("gold") == Some(20));
+        assert!(loot.get_file("item") == Some(PathBuf::from("items/staff.png").as_path()));
+
+        let xml = format!("<map {}><properties><property name=\"side\" propertytype=\"Faction\" value=\"Elves\"/></properties></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml).unwrap().apply_project(&project) {
+            Err(TmxError::UnknownValue { attribute, value, .. }) => {
+                assert!(attribute == "side");
+                assert!(value == "Elves");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_invalid_property() {
+        let xml = format!("<map {}><properties><property name=\"lives\" type=\"int\" value=\"many\"/></properties></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml) {
+            Err(TmxError::InvalidNumber { element, attribute, value }) => {
+                assert!(element == "property");
+                assert!(attribute == "value");
+                assert!(value == "many");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let xml = format!("<map {}><properties><property name=\"fog\" type=\"color\" value=\"red\"/></properties></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml) {
+            Err(TmxError::UnknownValue { element, value, .. }) => {
+                assert!(element == "property");
+                assert!(value == "red");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_optional_map_attributes() {
+        let map = Map::parse(&format!("<map {}></map>", MAP_ATTRIBUTES)).unwrap();
+        assert!(map.backgroundcolor.is_none());
+        assert!(map.nextobjectid == 1);
+
+        let mut written = Vec::new();
+        map.write_to(&mut written).unwrap();
+        assert!(!String::from_utf8(written).unwrap().contains("backgroundcolor"));
+    }
+
+    #[test]
+    fn test_error_xml() {
+        let xml = format!("<map {}><layer name=\"a\" width=\"2\" height=\"1\"></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml) {
+            Err(TmxError::Xml { position, .. }) => assert!(position > 0),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_xml_attribute() {
+        let xml = format!("<map {}>\n <layer name=\"a\" width=2 height=\"1\"><data/></layer>\n</map>", MAP_ATTRIBUTES);
+        let end_of_layer_tag = xml.find("<layer").unwrap() + xml[xml.find("<layer").unwrap()..].find('>').unwrap() + 1;
+
+        match Map::parse(&xml) {
+            Err(TmxError::Xml { position, .. }) => assert!(position == end_of_layer_tag, "{}", position),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_missing_attribute() {
+        let xml = format!("<map {}><layer name=\"a\" height=\"1\"><data/></layer></map>", MAP_ATTRIBUTES);
+
+        match Map::parse(&xml) {
+            Err(TmxError::MissingAttribute { element, attribute }) => {
+                assert!(element == "layer");
+                assert!(attribute == "width");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_invalid_number() {
+        let xml = format!("<map {}></map>", MAP_ATTRIBUTES).replace(r#"width="2""#, r#"width="wide""#);
+
+        let error = Map::parse(&xml).unwrap_err();
+        match &error {
+            TmxError::InvalidNumber { element, attribute, value } => {
+                assert!(element == "map");
+                assert!(attribute == "width");
+                assert!(value == "wide");
+            },
+            other => panic!("Unexpected error {:?}", other),
+        }
+        assert!(error.to_string() == "Attribute 'width' on <map> is not a valid number: 'wide'");
+    }
+
+    #[test]
+    fn test_error_unknown_value() {
+        let xml = format!("<map {}></map>", MAP_ATTRIBUTES).replace("orthogonal", "diagonal");
+
+        match Map::parse(&xml) {
+            Err(TmxError::UnknownValue { element, attribute, value }) => {
+                assert!(element == "map");
+                assert!(attribute == "orientation");
+                assert!(value == "diagonal");
+            },
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_invalid_tile_data() {
+        let layer = |width: i32, height: i32, data: &str| format!("<map {}><layer name=\"a\" width=\"{}\" height=\"{}\"><data encoding=\"csv\">{}</data></layer></map>",
+            MAP_ATTRIBUTES, width, height, data);
+
+        for xml in &[layer(2, 1, "1,x"), layer(-2, -1, "1,2"), layer(65536, 65536, "1")] {
+            match Map::parse(xml) {
+                Err(TmxError::InvalidTileData(_)) => (),
+                other => panic!("Unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_invalid_chunks() {
+        let chunk = |x: i32, size: i32| format!("<chunk x=\"{}\" y=\"0\" width=\"{}\" height=\"1\">{}</chunk>", x, size, vec!["1"; size as usize].join(","));
+
+        for chunks in &[chunk(1, 2), chunk(0, 2) + &chunk(2, 1), chunk(0, 2) + &chunk(0, 2), chunk(i32::MAX, 1)] {
+            let xml = format!("<map {} infinite=\"1\"><layer name=\"a\" width=\"2\" height=\"1\"><data encoding=\"csv\">{}</data></layer></map>", MAP_ATTRIBUTES, chunks);
+
+            match Map::parse(&xml) {
+                Err(TmxError::InvalidTileData(_)) => (),
+                other => panic!("Unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_missing_map() {
+        match Map::parse("<tileset/>") {
+            Err(TmxError::MissingElement(element)) => assert!(element == "map"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn test_layers(layers: &[&Layer]) {
+
+        let ground = layers.first().unwrap();
+
+        assert!(ground.id == 1);
+        assert!(ground.name == "Ground");
+        assert!(ground.width == 40);
+        assert!(ground.height == 40);
+        assert!(ground.opacity == 1.0);
+        assert!(ground.visible);
+        assert!(ground.offsetx == 0);
+        assert!(ground.offsety == 0);
+        assert!(ground.properties.is_empty());
+        assert!(ground.data.len() == 40 * 40);
+
+        for y in 0..40 {
+            for x in 0..40 {
+                assert!(ground.gid_at(x, y) == Some(((x * 7 + y * 3) % 49) as i64));
+            }
+        }
+
+        assert!(ground.data[41].flipped_horizontally());
+        assert!(ground.gid_at(-1, 0).is_none());
+        assert!(ground.gid_at(0, 40).is_none());
+        assert!(ground.gid_at(40, 0).is_none());
+
+        let overlay = layers.get(1).unwrap();
+
+        assert!(overlay.name == "Overlay");
+        assert!(overlay.opacity == 0.5);
+        assert!(!overlay.visible);
+        assert!(overlay.offsetx == 4);
+        assert!(overlay.offsety == -8);
+        assert!(overlay.gid_at(3, 5) == Some(42));
+        assert!(overlay.data.iter().filter(|gid| !gid.is_empty()).count() == 1);
+
+        assert!(overlay.properties.len() == 1);
+        assert!(overlay.properties.get("collides") == Some(&PropertyValue::Bool(true)));
     }
 }
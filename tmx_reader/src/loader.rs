@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Provides the content of files referenced from a map, like external `.tsx` tilesets.
+pub trait ResourceLoader {
+    /// Returns the content of the file at `path`, which is given exactly as it appears in the map.
+    fn load(&self, path: &str) -> io::Result<Cow<'_, [u8]>>;
+}
+
+/// Loads resources from the filesystem, relative to a base directory.
+#[derive(Debug)]
+pub struct FileSystemLoader {
+    pub base: PathBuf,
+}
+
+impl FileSystemLoader {
+    pub fn new<P: Into<PathBuf>>(base: P) -> FileSystemLoader {
+        FileSystemLoader { base: base.into() }
+    }
+}
+
+impl ResourceLoader for FileSystemLoader {
+    fn load(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        fs::read(self.base.join(path)).map(Cow::Owned)
+    }
+}
+
+/// Loads resources from memory, e.g. files embedded with `include_bytes!`.
+impl ResourceLoader for HashMap<&str, &[u8]> {
+    fn load(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        self.get(path)
+            .map(|content| Cow::Borrowed(*content))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown resource: {}", path)))
+    }
+}
+
+/// Resolves paths relative to the file at `base`, like the tilesets referenced from a map in another directory.
+pub(crate) struct RelativeLoader<'a> {
+    pub loader: &'a dyn ResourceLoader,
+    pub base: &'a str,
+}
+
+impl ResourceLoader for RelativeLoader<'_> {
+    fn load(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        self.loader.load(&crate::join_path(self.base, path))
+    }
+}
+
+/// Used when a map is parsed without a loader, every external reference fails to load.
+pub(crate) struct NoLoader;
+
+impl ResourceLoader for NoLoader {
+    fn load(&self, _path: &str) -> io::Result<Cow<'_, [u8]>> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "External resources require a ResourceLoader"))
+    }
+}
@@ -0,0 +1,260 @@
+#[cfg(feature = "json")]
+use std::path::PathBuf;
+#[cfg(feature = "json")]
+use std::str;
+
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+use crate::{Properties, Property, PropertyValue, TmxError};
+use crate::properties::merge_members;
+#[cfg(feature = "json")]
+use crate::{ResourceLoader, Rgba};
+
+/// The custom property types defined in a Tiled project (`.tiled-project`).
+///
+/// Maps only store the members of a class that differ from its defaults and enums as plain strings or ints,
+/// `Map::apply_project` uses these definitions to fill in the rest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Project {
+    pub propertytypes: Vec<CustomType>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomType {
+    Class(ClassType),
+    Enum(EnumType),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassType {
+    pub id: i64,
+    pub name: String,
+    /// Every member with its default value.
+    pub members: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumType {
+    pub id: i64,
+    pub name: String,
+    pub storagetype: EnumStorage,
+    pub values: Vec<String>,
+    pub valuesasflags: bool,
+}
+
+/// How the value of an enum property is written to the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnumStorage {
+    /// The name of the value, or the comma separated names if the enum is used as flags.
+    String,
+    /// The index of the value, or a bitmask of the indices if the enum is used as flags.
+    Int,
+}
+
+impl CustomType {
+    pub fn name(&self) -> &str {
+        match self {
+            CustomType::Class(class) => &class.name,
+            CustomType::Enum(enumeration) => &enumeration.name,
+        }
+    }
+}
+
+impl ClassType {
+    /// Combines the members set on an instance with the defaults of the class, in the order of the class.
+    fn merge(&self, members: &Properties) -> Properties {
+        merge_members(&self.members, members)
+    }
+}
+
+impl EnumType {
+    fn resolve(&self, value: &PropertyValue) -> Option<Vec<String>> {
+        let names: Vec<String> = match value {
+            PropertyValue::Enum(names) => names.clone(),
+            PropertyValue::String(name) if self.valuesasflags => name.split(',')
+                .filter(|n| !n.is_empty())
+                .map(String::from)
+                .collect(),
+            PropertyValue::String(name) => vec![name.clone()],
+            PropertyValue::Int(flags) if self.valuesasflags => {
+                if *flags < 0 || flags.checked_shr(self.values.len() as u32).unwrap_or(0) != 0 {
+                    return None;
+                }
+                self.values.iter()
+                    .enumerate()
+                    .filter(|(bit, _)| flags & (1 << bit) != 0)
+                    .map(|(_, name)| name.clone())
+                    .collect()
+            },
+            PropertyValue::Int(index) => vec![self.values.get(*index as usize)?.clone()],
+            _ => return None,
+        };
+
+        if names.iter().all(|n| self.values.contains(n)) {
+            Some(names)
+        } else {
+            None
+        }
+    }
+}
+
+impl Project {
+    /// Parses the content of a `.tiled-project` file.
+    #[cfg(feature = "json")]
+    pub fn parse(json: &str) -> Result<Project, TmxError> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| TmxError::InvalidProject(e.to_string()))?;
+
+        let raw = root["propertyTypes"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+
+        Ok(Project {
+            propertytypes: raw.iter()
+                .map(|t| custom_type_from_json(t, raw))
+                .collect::<Result<Vec<CustomType>, TmxError>>()?,
+        })
+    }
+
+    /// Loads and parses the `.tiled-project` file at `path`.
+    #[cfg(feature = "json")]
+    pub fn load(path: &str, loader: &dyn ResourceLoader) -> Result<Project, TmxError> {
+        let content = loader.load(path)
+            .map_err(|error| TmxError::Resource { path: path.to_string(), error })?;
+        let content = str::from_utf8(&content)
+            .map_err(|e| TmxError::InvalidProject(e.to_string()))?;
+
+        Project::parse(content)
+    }
+
+    pub fn custom_type(&self, name: &str) -> Option<&CustomType> {
+        self.propertytypes.iter().find(|t| t.name() == name)
+    }
+
+    /// Fills in the missing members of class properties and resolves enum properties, nested ones included.
+    /// Properties without a known `propertytype` are left untouched.
+    pub fn resolve(&self, properties: &mut Properties) -> Result<(), TmxError> {
+        for property in properties.iter_mut() {
+            self.resolve_property(property)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_property(&self, property: &mut Property) -> Result<(), TmxError> {
+        let custom_type = match property.propertytype.as_ref().and_then(|name| self.custom_type(name)) {
+            Some(custom_type) => custom_type,
+            None => return Ok(()),
+        };
+
+        match custom_type {
+            CustomType::Class(class) => {
+                if let PropertyValue::Class(members) = &mut property.value {
+                    *members = class.merge(members);
+                    self.resolve(members)?;
+                }
+            },
+            CustomType::Enum(enumeration) => match enumeration.resolve(&property.value) {
+                Some(names) => property.value = PropertyValue::Enum(names),
+                None => return Err(TmxError::UnknownValue {
+                    element: "property".to_string(),
+                    attribute: property.name.clone(),
+                    value: match &property.value {
+                        PropertyValue::String(value) => value.clone(),
+                        PropertyValue::Int(value) => value.to_string(),
+                        other => format!("{:?}", other),
+                    },
+                }),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+fn custom_type_from_json(custom_type: &Value, raw: &[Value]) -> Result<CustomType, TmxError> {
+    let id = custom_type["id"].as_i64().unwrap_or(0);
+    let name = custom_type["name"].as_str()
+        .ok_or_else(|| TmxError::InvalidProject("Property type without a name".to_string()))?
+        .to_string();
+
+    match custom_type["type"].as_str() {
+        Some("class") => {
+            let mut members = Properties::default();
+            for member in custom_type["members"].as_array().map(Vec::as_slice).unwrap_or(&[]) {
+                members.push(member_from_json(member, raw)?);
+            }
+            Ok(CustomType::Class(ClassType { id, name, members }))
+        },
+        Some("enum") => Ok(CustomType::Enum(EnumType {
+            id,
+            storagetype: match custom_type["storageType"].as_str() {
+                Some("int") => EnumStorage::Int,
+                _ => EnumStorage::String,
+            },
+            values: custom_type["values"].as_array().map(Vec::as_slice).unwrap_or(&[])
+                .iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect(),
+            valuesasflags: custom_type["valuesAsFlags"].as_bool().unwrap_or(false),
+            name,
+        })),
+        other => Err(TmxError::InvalidProject(format!("Unknown kind of property type '{}': {:?}", name, other))),
+    }
+}
+
+#[cfg(feature = "json")]
+fn member_from_json(member: &Value, raw: &[Value]) -> Result<Property, TmxError> {
+    let propertytype = member["propertyType"].as_str();
+
+    Ok(Property {
+        name: member["name"].as_str()
+            .ok_or_else(|| TmxError::InvalidProject("Class member without a name".to_string()))?
+            .to_string(),
+        propertytype: propertytype.map(String::from),
+        value: value_from_json(member["type"].as_str().unwrap_or("string"), propertytype, &member["value"], raw)?,
+    })
+}
+
+#[cfg(feature = "json")]
+fn value_from_json(kind: &str, propertytype: Option<&str>, value: &Value, raw: &[Value]) -> Result<PropertyValue, TmxError> {
+    let invalid = || TmxError::InvalidProject(format!("Invalid {} value: {}", kind, value));
+
+    Ok(match kind {
+        "string"    => PropertyValue::String(value.as_str().ok_or_else(invalid)?.to_string()),
+        "int"       => PropertyValue::Int(value.as_i64().ok_or_else(invalid)?),
+        "float"     => PropertyValue::Float(value.as_f64().ok_or_else(invalid)?),
+        "bool"      => PropertyValue::Bool(value.as_bool().ok_or_else(invalid)?),
+        "color"     => match value.as_str().ok_or_else(invalid)? {
+            ""      => PropertyValue::Color(Rgba { r: 0, g: 0, b: 0, a: 0 }),
+            color   => PropertyValue::Color(Rgba::from_hex(color).ok_or_else(invalid)?),
+        },
+        "file"      => PropertyValue::File(PathBuf::from(value.as_str().ok_or_else(invalid)?)),
+        "object"    => PropertyValue::Object(value.as_i64().ok_or_else(invalid)?),
+        "class"     => {
+            // Like in a map, only the members that differ from the defaults of the class are written,
+            // their types come from the definition of the class.
+            let definitions = raw.iter()
+                .find(|t| propertytype.is_some() && t["name"].as_str() == propertytype)
+                .and_then(|t| t["members"].as_array())
+                .map(Vec::as_slice)
+                .unwrap_or(&[]);
+
+            let mut members = Properties::default();
+            for (name, value) in value.as_object().ok_or_else(invalid)? {
+                let definition = definitions.iter()
+                    .find(|d| d["name"].as_str() == Some(name))
+                    .ok_or_else(|| TmxError::InvalidProject(format!("Unknown member '{}' of class {:?}", name, propertytype)))?;
+                let member_type = definition["propertyType"].as_str();
+
+                members.push(Property {
+                    name: name.clone(),
+                    propertytype: member_type.map(String::from),
+                    value: value_from_json(definition["type"].as_str().unwrap_or("string"), member_type, value, raw)?,
+                });
+            }
+            PropertyValue::Class(members)
+        },
+        other       => return Err(TmxError::InvalidProject(format!("Unknown property type '{}'", other))),
+    })
+}
@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+use std::slice;
+
+use crate::{PropertyType, Rgba};
+
+/// The value of a custom property, already converted according to its `type`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Color(Rgba),
+    File(PathBuf),
+    /// The id of the referenced object, 0 if no object is referenced.
+    Object(i64),
+    /// The members of a custom class, the name of the class is the `propertytype` of the property.
+    Class(Properties),
+    /// The names of the selected values of a custom enum, only set once a `Project` resolved it.
+    /// Holds a single name unless the enum allows selecting several values as flags.
+    Enum(Vec<String>),
+}
+
+impl PropertyValue {
+    pub fn property_type(&self) -> PropertyType {
+        match self {
+            PropertyValue::String(_) => PropertyType::String,
+            PropertyValue::Int(_) => PropertyType::Int,
+            PropertyValue::Float(_) => PropertyType::Float,
+            PropertyValue::Bool(_) => PropertyType::Bool,
+            PropertyValue::Color(_) => PropertyType::Color,
+            PropertyValue::File(_) => PropertyType::File,
+            PropertyValue::Object(_) => PropertyType::Object,
+            PropertyValue::Class(_) => PropertyType::Class,
+            PropertyValue::Enum(_) => PropertyType::Enum,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    /// The name of the custom class or enum this property is an instance of.
+    pub propertytype: Option<String>,
+    pub value: PropertyValue,
+}
+
+/// The custom properties of a map, layer, object ... in the order they appear in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Properties {
+    properties: Vec<Property>,
+}
+
+impl Properties {
+    pub fn new(properties: Vec<Property>) -> Properties {
+        Properties { properties }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.iter().find(|p| p.name == name).map(|p| &p.value)
+    }
+
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(PropertyValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(PropertyValue::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        match self.get(name) {
+            Some(PropertyValue::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(PropertyValue::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_color(&self, name: &str) -> Option<Rgba> {
+        match self.get(name) {
+            Some(PropertyValue::Color(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_file(&self, name: &str) -> Option<&Path> {
+        match self.get(name) {
+            Some(PropertyValue::File(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_object(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(PropertyValue::Object(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_class(&self, name: &str) -> Option<&Properties> {
+        match self.get(name) {
+            Some(PropertyValue::Class(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_enum(&self, name: &str) -> Option<&[String]> {
+        match self.get(name) {
+            Some(PropertyValue::Enum(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn push(&mut self, property: Property) {
+        self.properties.push(property);
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Property> {
+        self.properties.iter_mut()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Property> {
+        self.properties.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Properties {
+    type Item = &'a Property;
+    type IntoIter = slice::Iter<'a, Property>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.properties.iter()
+    }
+}
+
+/// Combines `members` with `defaults` in the order of the defaults, used for class defaults and templates.
+/// Members of nested classes are merged as well, an instance only holds the ones that were changed.
+pub(crate) fn merge_members(defaults: &Properties, members: &Properties) -> Properties {
+    let mut merged = Properties::default();
+
+    for default in defaults.iter() {
+        match members.iter().find(|m| m.name == default.name) {
+            Some(member) => merged.push(Property {
+                name: member.name.clone(),
+                propertytype: member.propertytype.clone().or_else(|| default.propertytype.clone()),
+                value: match (&default.value, &member.value) {
+                    (PropertyValue::Class(defaults), PropertyValue::Class(members)) =>
+                        PropertyValue::Class(merge_members(defaults, members)),
+                    (default, value) => convert(value, default),
+                },
+            }),
+            None => merged.push(default.clone()),
+        }
+    }
+
+    for member in members.iter().filter(|m| defaults.get(&m.name).is_none()) {
+        merged.push(member.clone());
+    }
+
+    merged
+}
+
+/// Members of classes in JSON maps don't state their type, they get the one of their default.
+fn convert(value: &PropertyValue, default: &PropertyValue) -> PropertyValue {
+    match (default, value) {
+        (PropertyValue::Float(_), PropertyValue::Int(value)) => PropertyValue::Float(*value as f64),
+        (PropertyValue::Object(_), PropertyValue::Int(value)) => PropertyValue::Object(*value),
+        (PropertyValue::File(_), PropertyValue::String(value)) => PropertyValue::File(PathBuf::from(value)),
+        (PropertyValue::Color(_), PropertyValue::String(value)) if value.is_empty() =>
+            PropertyValue::Color(Rgba { r: 0, g: 0, b: 0, a: 0 }),
+        (PropertyValue::Color(_), PropertyValue::String(value)) =>
+            Rgba::from_hex(value).map(PropertyValue::Color).unwrap_or_else(|| PropertyValue::String(value.clone())),
+        (_, value) => value.clone(),
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{Gid, Layer, Map, Orientation, Rect, Renderorder, StaggerAxis, StaggerIndex};
+
+/// How much the tiles of the map can be drawn beyond the bounding box of their cell, in pixels.
+/// Tiles larger than the grid reach up and to the right, tile offsets move them in any direction.
+#[derive(Debug, Clone, Copy, Default)]
+struct DrawMargins {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+impl DrawMargins {
+    fn new(map: &Map) -> DrawMargins {
+        let mut margins = DrawMargins::default();
+        let (mut maxwidth, mut maxheight) = (map.tilewidth, map.tileheight);
+
+        for tileset in &map.tilesets {
+            maxwidth = maxwidth.max(tileset.tilewidth);
+            maxheight = maxheight.max(tileset.tileheight);
+            for image in tileset.tiles.iter().filter_map(|tile| tile.image.as_ref()) {
+                maxwidth = maxwidth.max(image.width);
+                maxheight = maxheight.max(image.height.unwrap_or(0));
+            }
+            if let Some(offset) = &tileset.tileoffset {
+                let (x, y) = (offset.x as i32, offset.y as i32);
+                margins.left = margins.left.max(-x);
+                margins.top = margins.top.max(-y);
+                margins.right = margins.right.max(x);
+                margins.bottom = margins.bottom.max(y);
+            }
+        }
+
+        margins.top += maxheight - map.tileheight;
+        margins.right += maxwidth - map.tilewidth;
+        margins
+    }
+
+    /// Grows `area` by the margins in the opposite direction, so it holds every cell whose tile reaches into `area`.
+    fn grow(&self, area: Rect) -> Rect {
+        Rect {
+            x: area.x - self.right,
+            y: area.y - self.bottom,
+            width: area.width + self.left + self.right,
+            height: area.height + self.top + self.bottom,
+        }
+    }
+}
+
+impl Map {
+    /// Returns the non-empty cells of a tile layer as `(x, y, gid)`, in the order they have to be drawn.
+    ///
+    /// The map is drawn row by row, top to bottom for `RightDown` and `LeftDown`, bottom to top for the other
+    /// render orders, each row from left to right or from right to left. Rows are the rows on screen:
+    /// in isometric maps they run diagonally through the tile grid, in maps staggered along x they
+    /// hold every other column.
+    ///
+    /// With a `viewport`, in pixels relative to the layer's offsets, only the cells whose tile can be seen
+    /// in it are yielded. Tiles larger than the grid and tile offsets are taken into account.
+    pub fn render_cells<'a>(&'a self, layer: &'a Layer, viewport: Option<Rect>) -> impl Iterator<Item = (i32, i32, Gid)> + 'a {
+        let bounds = layer.bounds();
+        let visible = viewport.map(|viewport| DrawMargins::new(self).grow(viewport));
+
+        let area = match visible {
+            Some(visible) => intersection(bounds, self.tiles_in(visible)),
+            None => bounds,
+        };
+
+        self.render_order(area)
+            .filter_map(move |(x, y)| layer.raw_gid_at(x, y).map(|gid| (x, y, gid)))
+            .filter(|(_, _, gid)| !gid.is_empty())
+            .filter(move |(x, y, _)| visible.map(|visible| self.tile_bounds(*x, *y).overlaps(visible)).unwrap_or(true))
+    }
+
+    /// The bounding box of a cell in pixels.
+    fn tile_bounds(&self, x: i32, y: i32) -> Rect {
+        let (left, top) = self.tile_to_pixel(x, y);
+        Rect { x: left.floor() as i32, y: top.floor() as i32, width: self.tilewidth, height: self.tileheight }
+    }
+
+    /// The rectangle of cells holding every cell that overlaps `area`, which is in pixels.
+    fn tiles_in(&self, area: Rect) -> Rect {
+        let corners = [
+            (area.x, area.y),
+            (area.x + area.width, area.y),
+            (area.x, area.y + area.height),
+            (area.x + area.width, area.y + area.height),
+        ];
+        let tiles: Vec<(i32, i32)> = corners.iter().map(|(x, y)| self.pixel_to_tile(*x as f32, *y as f32)).collect();
+
+        // Staggered rows and columns reach half a tile beyond the corners.
+        let left = tiles.iter().map(|t| t.0).min().unwrap_or(0) - 1;
+        let top = tiles.iter().map(|t| t.1).min().unwrap_or(0) - 1;
+        let right = tiles.iter().map(|t| t.0).max().unwrap_or(0) + 1;
+        let bottom = tiles.iter().map(|t| t.1).max().unwrap_or(0) + 1;
+
+        Rect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 }
+    }
+
+    /// Every cell of `area` in the render order of the map.
+    fn render_order(&self, area: Rect) -> Box<dyn Iterator<Item = (i32, i32)>> {
+        let (down, right) = match self.renderorder {
+            Renderorder::RightDown => (true, true),
+            Renderorder::RightUp => (false, true),
+            Renderorder::LeftDown => (true, false),
+            Renderorder::LeftUp => (false, false),
+        };
+        let rows = ordered(area.y, area.y + area.height, down);
+        let columns = move || ordered(area.x, area.x + area.width, right);
+
+        match self.orientation {
+            Orientation::Isometric => {
+                // Cells on the same diagonal x + y share a row on screen, x grows to the right.
+                let diagonals = ordered(area.x + area.y, area.x + area.y + area.width + area.height - 1, down);
+                Box::new(diagonals.flat_map(move |diagonal| columns()
+                    .map(move |x| (x, diagonal - x))
+                    .filter(move |(_, y)| *y >= area.y && *y < area.y + area.height)))
+            },
+            Orientation::Staggered | Orientation::Hexagonal if self.staggeraxis == StaggerAxis::X => {
+                // Every row is drawn as two rows on screen, the shifted columns are half a tile lower.
+                let staggered = self.staggered_columns();
+                Box::new(rows.flat_map(move |y| {
+                    let halves = if down { vec![false, true] } else { vec![true, false] };
+                    halves.into_iter().flat_map(move |shifted| columns()
+                        .filter(move |x| staggered(*x) == shifted)
+                        .map(move |x| (x, y)))
+                }))
+            },
+            _ => Box::new(rows.flat_map(move |y| columns().map(move |x| (x, y)))),
+        }
+    }
+
+    /// Whether a column of a map staggered along x is shifted down by half a tile.
+    fn staggered_columns(&self) -> impl Fn(i32) -> bool + Copy {
+        let even = self.staggerindex == StaggerIndex::Even;
+        move |x: i32| (x & 1 == 1) != even
+    }
+}
+
+/// The numbers from `start` up to `end`, excluded, in ascending or descending order.
+fn ordered(start: i32, end: i32, ascending: bool) -> Box<dyn Iterator<Item = i32>> {
+    if ascending {
+        Box::new(start..end)
+    } else {
+        Box::new((start..end).rev())
+    }
+}
+
+fn intersection(a: Rect, b: Rect) -> Rect {
+    let left = a.x.max(b.x);
+    let top = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+    Rect { x: left, y: top, width: (right - left).max(0), height: (bottom - top).max(0) }
+}
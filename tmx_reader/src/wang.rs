@@ -0,0 +1,206 @@
+use crate::{FlipFlags, Gid, Properties, Rgba, Tileset};
+
+/// Which parts of its tiles the colors of a Wang set describe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WangSetType {
+    Corner,
+    Edge,
+    /// Corners and edges.
+    Mixed,
+}
+
+impl WangSetType {
+    fn uses(self, position: usize) -> bool {
+        match self {
+            WangSetType::Corner => !position.is_multiple_of(2),
+            WangSetType::Edge => position.is_multiple_of(2),
+            WangSetType::Mixed => true,
+        }
+    }
+}
+
+/// A terrain set, which tells Tiled how the tiles of a tileset fit together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WangSet {
+    pub name: String,
+    pub class: Option<String>,
+    pub wangsettype: WangSetType,
+    /// The local id of the tile representing the set.
+    pub tile: Option<i64>,
+    /// The color `i` of a `WangId` is `colors[i - 1]`.
+    pub colors: Vec<WangColor>,
+    pub tiles: Vec<WangTile>,
+    pub properties: Properties,
+}
+
+impl WangSet {
+    /// Returns the Wang id of the given local tile id, if the tile is part of the set.
+    pub fn wangid(&self, tileid: i64) -> Option<WangId> {
+        self.tiles.iter().find(|tile| tile.tileid == tileid).map(|tile| tile.wangid)
+    }
+}
+
+/// A single terrain of a Wang set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WangColor {
+    pub name: String,
+    pub class: Option<String>,
+    /// The colour the terrain is shown with in Tiled.
+    pub color: Rgba,
+    /// The local id of the tile representing the terrain.
+    pub tile: Option<i64>,
+    /// How likely tiles showing this terrain are picked, relative to the other terrains.
+    pub probability: f32,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WangTile {
+    pub tileid: i64,
+    pub wangid: WangId,
+}
+
+/// The colors of the edges and corners of a tile, clockwise starting at the top edge.
+///
+/// The values are indices into `WangSet::colors` starting at 1, 0 means the position has no color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WangId(pub [u8; 8]);
+
+impl WangId {
+    pub const TOP: usize = 0;
+    pub const TOP_RIGHT: usize = 1;
+    pub const RIGHT: usize = 2;
+    pub const BOTTOM_RIGHT: usize = 3;
+    pub const BOTTOM: usize = 4;
+    pub const BOTTOM_LEFT: usize = 5;
+    pub const LEFT: usize = 6;
+    pub const TOP_LEFT: usize = 7;
+
+    pub fn color(&self, position: usize) -> u8 {
+        self.0[position]
+    }
+}
+
+/// The position on a tile in half tiles from its top left corner, (1, 1) is the center.
+const POINTS: [(i32, i32); 8] = [(1, 0), (2, 0), (2, 1), (2, 2), (1, 2), (0, 2), (0, 1), (0, 0)];
+
+/// The offsets of the eight cells around a cell.
+const NEIGHBOURS: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+/// Whether `position` of a tile is also an edge or corner of the tile `dx`, `dy` cells away.
+fn shares_position(position: usize, dx: i32, dy: i32) -> bool {
+    let (x, y) = POINTS[position];
+    let point = (x - 2 * dx, y - 2 * dy);
+    POINTS.contains(&point)
+}
+
+/// A terrain of the tilesets written before Tiled 1.5, which replaced them by Wang sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainType {
+    pub name: String,
+    /// The local id of the tile representing the terrain.
+    pub tile: i64,
+    pub properties: Properties,
+}
+
+impl Tileset {
+    pub fn wangset(&self, name: &str) -> Option<&WangSet> {
+        self.wangsets.iter().find(|wangset| wangset.name == name)
+    }
+
+    /// Picks the tiles of `wangset` for a grid of terrain colors.
+    ///
+    /// `colors` holds the color of each cell in row-major order, rows are `width` cells long.
+    /// Edges and corners shared only by cells of one color get that color. Where different colors meet,
+    /// corners get the highest color, so terrains with a higher index are drawn over the ones with a lower index.
+    /// Edges get the highest color as well in mixed sets, in edge sets they stay unconnected.
+    ///
+    /// Tiles matching best are picked, among those one is chosen at random, weighted by `Tile::probability` and
+    /// the probability of its colors like Tiled does. `random` has to return numbers in `0.0..1.0`.
+    ///
+    /// Returns the global tile ids, ready to be used as `Layer::data`. Cells of color 0 stay empty, so do cells whose
+    /// tile has an id too large for a gid.
+    /// Flipped or rotated variants of the tiles aren't considered.
+    pub fn autotile(&self, wangset: &WangSet, colors: &[u8], width: usize, mut random: impl FnMut() -> f32) -> Vec<Gid> {
+        let height = colors.len().checked_div(width).unwrap_or(0);
+        let color_at = |x: i32, y: i32| -> Option<u8> {
+            if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                None
+            } else {
+                Some(colors[y as usize * width + x as usize])
+            }
+        };
+
+        let mut gids = vec![Gid::EMPTY; width * height];
+
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let color = colors[y as usize * width + x as usize];
+                if color == 0 {
+                    continue;
+                }
+
+                let mut wanted = WangId::default();
+                for position in (0..8).filter(|p| wangset.wangsettype.uses(*p)) {
+                    // Cells beyond the grid count as the same color.
+                    let shared: Vec<u8> = NEIGHBOURS.iter()
+                        .filter(|(dx, dy)| shares_position(position, *dx, *dy))
+                        .filter_map(|(dx, dy)| color_at(x + dx, y + dy))
+                        .collect();
+
+                    wanted.0[position] = if shared.iter().all(|c| *c == color) {
+                        color
+                    } else if wangset.wangsettype == WangSetType::Edge {
+                        0
+                    } else {
+                        shared.into_iter().fold(color, u8::max)
+                    };
+                }
+
+                let tile = self.pick_wang_tile(wangset, wanted, &mut random);
+                if let Some(gid) = tile.and_then(|tile| Gid::new(self.firstgid + tile.tileid, FlipFlags::default())) {
+                    gids[y as usize * width + x as usize] = gid;
+                }
+            }
+        }
+
+        gids
+    }
+
+    /// Returns one of the tiles matching `wanted` best.
+    fn pick_wang_tile<'a>(&self, wangset: &'a WangSet, wanted: WangId, random: &mut impl FnMut() -> f32) -> Option<&'a WangTile> {
+        let mismatches = |tile: &WangTile| (0..8)
+            .filter(|p| wangset.wangsettype.uses(*p) && tile.wangid.0[*p] != wanted.0[*p])
+            .count();
+
+        let best = wangset.tiles.iter().map(mismatches).min()?;
+        let candidates: Vec<(&WangTile, f32)> = wangset.tiles.iter()
+            .filter(|tile| mismatches(tile) == best)
+            .map(|tile| (tile, self.wang_probability(wangset, tile)))
+            .collect();
+
+        let total: f32 = candidates.iter().map(|(_, probability)| probability).sum();
+        if total <= 0.0 {
+            return candidates.first().map(|(tile, _)| *tile);
+        }
+
+        let mut remaining = random() * total;
+        for (tile, probability) in &candidates {
+            if remaining < *probability {
+                return Some(tile);
+            }
+            remaining -= probability;
+        }
+        candidates.last().map(|(tile, _)| *tile)
+    }
+
+    fn wang_probability(&self, wangset: &WangSet, tile: &WangTile) -> f32 {
+        let colors: f32 = (0..8)
+            .filter(|p| wangset.wangsettype.uses(*p))
+            .filter_map(|p| wangset.colors.get(usize::from(tile.wangid.0[p]).checked_sub(1)?))
+            .map(|color| color.probability)
+            .product();
+
+        self.tile(tile.tileid).map(|t| t.probability).unwrap_or(1.0) * colors
+    }
+}
@@ -0,0 +1,190 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::loader::RelativeLoader;
+use crate::{Map, Rect, ResourceLoader, TmxError};
+
+/// A Tiled world (`.world`), which places several maps next to each other in world space.
+///
+/// Maps aren't loaded together with the world, `World::load_map` loads them one at a time, e.g. the ones
+/// `World::intersecting` returns for the area around the player.
+#[derive(Debug, Clone)]
+pub struct World {
+    /// The maps listed in the file and the ones added through `add_matching_maps`.
+    pub maps: Vec<WorldMap>,
+    pub patterns: Vec<WorldPattern>,
+    pub onlyshowadjacentmaps: bool,
+}
+
+/// A map and where it is placed, in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMap {
+    /// The path of the map file, relative to the world file.
+    pub filename: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Places every map whose file name matches `regexp`, the first two captured numbers are its column and row.
+#[derive(Debug, Clone)]
+pub struct WorldPattern {
+    pub regexp: Regex,
+    pub multiplierx: i32,
+    pub multipliery: i32,
+    pub offsetx: i32,
+    pub offsety: i32,
+    pub mapwidth: i32,
+    pub mapheight: i32,
+}
+
+impl WorldMap {
+    pub fn rect(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: self.width, height: self.height }
+    }
+}
+
+impl WorldPattern {
+    /// Returns where the map with the given file name is placed, if the name matches the pattern.
+    /// Names placing the map beyond the range of `i32` don't match.
+    pub fn place(&self, filename: &str) -> Option<WorldMap> {
+        let captures = self.regexp.captures(filename)?;
+        let column: i32 = captures.get(1)?.as_str().parse().ok()?;
+        let row: i32 = captures.get(2)?.as_str().parse().ok()?;
+
+        Some(WorldMap {
+            filename: filename.to_string(),
+            x: column.checked_mul(self.multiplierx)?.checked_add(self.offsetx)?,
+            y: row.checked_mul(self.multipliery)?.checked_add(self.offsety)?,
+            width: self.mapwidth,
+            height: self.mapheight,
+        })
+    }
+}
+
+impl World {
+    /// Parses the content of a `.world` file. Patterns only place maps once their file names are passed to
+    /// `add_matching_maps`.
+    pub fn parse(json: &str) -> Result<World, TmxError> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| TmxError::InvalidWorld(e.to_string()))?;
+
+        Ok(World {
+            maps: array(&root, "maps")?.iter()
+                .map(world_map_from_json)
+                .collect::<Result<Vec<WorldMap>, TmxError>>()?,
+            patterns: array(&root, "patterns")?.iter()
+                .map(world_pattern_from_json)
+                .collect::<Result<Vec<WorldPattern>, TmxError>>()?,
+            onlyshowadjacentmaps: root["onlyShowAdjacentMaps"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Loads the `.world` file at `path`, the patterns are matched against the files in the same directory.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<World, TmxError> {
+        let path = path.as_ref();
+        let resource_error = |error| TmxError::Resource { path: path.to_string_lossy().to_string(), error };
+
+        let mut world = World::parse(&fs::read_to_string(path).map_err(resource_error)?)?;
+
+        if !world.patterns.is_empty() {
+            let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+            let mut filenames: Vec<String> = fs::read_dir(directory).map_err(resource_error)?
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect();
+            filenames.sort();
+            world.add_matching_maps(filenames.iter().map(String::as_str));
+        }
+
+        Ok(world)
+    }
+
+    /// Adds the maps placed by the patterns among the given file names, ones already in `maps` are skipped.
+    /// Used for worlds read through a `ResourceLoader`, which can't list the files next to the world.
+    pub fn add_matching_maps<'a>(&mut self, filenames: impl IntoIterator<Item = &'a str>) {
+        for filename in filenames {
+            if self.maps.iter().any(|map| map.filename == filename) {
+                continue;
+            }
+            if let Some(map) = self.patterns.iter().find_map(|pattern| pattern.place(filename)) {
+                self.maps.push(map);
+            }
+        }
+    }
+
+    /// Returns the maps overlapping the given rectangle of world space.
+    pub fn intersecting(&self, area: Rect) -> impl Iterator<Item = &WorldMap> {
+        self.maps.iter().filter(move |map| map.rect().overlaps(area))
+    }
+
+    /// Loads one of the maps of the world in either format. `loader` resolves paths relative to the world file,
+    /// the tilesets and templates of the map are loaded relative to the map.
+    pub fn load_map(&self, map: &WorldMap, loader: &dyn ResourceLoader) -> Result<Map, TmxError> {
+        let content = loader.load(&map.filename)
+            .map_err(|error| TmxError::Resource { path: map.filename.clone(), error })?;
+        let content = str::from_utf8(&content)
+            .map_err(|e| TmxError::Resource { path: map.filename.clone(), error: io::Error::new(io::ErrorKind::InvalidData, e) })?;
+
+        Map::parse_any_with_loader(content, &RelativeLoader { loader, base: &map.filename })
+    }
+}
+
+fn world_map_from_json(map: &Value) -> Result<WorldMap, TmxError> {
+    Ok(WorldMap {
+        filename: map["fileName"].as_str()
+            .ok_or_else(|| TmxError::InvalidWorld("Map without a fileName".to_string()))?
+            .to_string(),
+        x: int(map, "x")?,
+        y: int(map, "y")?,
+        width: int(map, "width")?,
+        height: int(map, "height")?,
+    })
+}
+
+fn world_pattern_from_json(pattern: &Value) -> Result<WorldPattern, TmxError> {
+    let regexp = pattern["regexp"].as_str()
+        .ok_or_else(|| TmxError::InvalidWorld("Pattern without a regexp".to_string()))?;
+    let multiplierx = int(pattern, "multiplierX")?;
+    let multipliery = int(pattern, "multiplierY")?;
+
+    Ok(WorldPattern {
+        regexp: Regex::new(regexp).map_err(|e| TmxError::InvalidWorld(e.to_string()))?,
+        multiplierx,
+        multipliery,
+        offsetx: int(pattern, "offsetX")?,
+        offsety: int(pattern, "offsetY")?,
+        // Worlds written before Tiled 1.9 don't have a map size, their maps fill the grid.
+        mapwidth: int_or(pattern, "mapWidth", multiplierx)?,
+        mapheight: int_or(pattern, "mapHeight", multipliery)?,
+    })
+}
+
+/// The number `key` of `value`, missing numbers are 0 like in Tiled.
+fn int(value: &Value, key: &str) -> Result<i32, TmxError> {
+    int_or(value, key, 0)
+}
+
+fn int_or(value: &Value, key: &str, default: i32) -> Result<i32, TmxError> {
+    match &value[key] {
+        Value::Null => Ok(default),
+        number => number.as_i64()
+            .and_then(|n| i32::try_from(n).ok())
+            .ok_or_else(|| TmxError::InvalidWorld(format!("'{}' is not a valid number: {}", key, number))),
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], TmxError> {
+    match &value[key] {
+        Value::Null => Ok(&[]),
+        Value::Array(values) => Ok(values),
+        other => Err(TmxError::InvalidWorld(format!("Expected an array for {}: {}", key, other))),
+    }
+}
@@ -0,0 +1,634 @@
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+
+use crate::encoding;
+use crate::{Compression, DataEncoding, DrawOrder, FillMode, Gid, Group, HorizontalAlignment, Image, Imagelayer, Layer,
+            LayerKind, Map, Object, ObjectAlignment, ObjectShape, Objectgroup, Orientation, Properties, Property,
+            PropertyValue, Renderorder, StaggerAxis, StaggerIndex, TerrainType, Text, Tile, TileRenderSize, Tileset,
+            TmxError, VerticalAlignment, WangColor, WangSet, WangSetType};
+
+impl Map {
+    /// Writes the map as TMX.
+    ///
+    /// Tile layers are written with their `encoding` and `compression`, external tilesets as references to
+    /// their `.tsx` files. Attributes holding their default value are left out, the way Tiled does it.
+    pub fn write_to<W: Write>(&self, output: W) -> Result<(), TmxError> {
+        let mut writer = TmxWriter { writer: Writer::new_with_indent(WriteAll(output), b' ', 1) };
+
+        writer.event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+        writer.map(self)?;
+        writer.writer.write(b"\n").map_err(TmxError::Write)?;
+        Ok(())
+    }
+}
+
+/// `quick_xml::Writer` ignores how many bytes a write took, so every write has to take all of them.
+struct WriteAll<W: Write>(W);
+
+impl<W: Write> Write for WriteAll<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// The attributes of an element, in the order they are written.
+#[derive(Default)]
+struct Attrs {
+    values: Vec<(&'static str, String)>,
+}
+
+impl Attrs {
+    fn new() -> Attrs {
+        Attrs::default()
+    }
+
+    fn set(mut self, name: &'static str, value: impl Display) -> Attrs {
+        self.values.push((name, value.to_string()));
+        self
+    }
+
+    fn set_if(self, condition: bool, name: &'static str, value: impl Display) -> Attrs {
+        if condition { self.set(name, value) } else { self }
+    }
+
+    fn set_opt(self, name: &'static str, value: Option<impl Display>) -> Attrs {
+        match value {
+            Some(value) => self.set(name, value),
+            None => self,
+        }
+    }
+
+    fn start(&self, name: &str) -> BytesStart<'static> {
+        let mut start = BytesStart::owned_name(name);
+        for (key, value) in &self.values {
+            start.push_attribute((*key, value.as_str()));
+        }
+        start
+    }
+}
+
+struct TmxWriter<W: Write> {
+    writer: Writer<WriteAll<W>>,
+}
+
+impl<W: Write> TmxWriter<W> {
+    fn event(&mut self, event: Event) -> Result<(), TmxError> {
+        self.writer.write_event(event).map(|_| ()).map_err(TmxError::Write)
+    }
+
+    fn empty(&mut self, name: &str, attrs: Attrs) -> Result<(), TmxError> {
+        self.event(Event::Empty(attrs.start(name)))
+    }
+
+    fn start(&mut self, name: &str, attrs: Attrs) -> Result<(), TmxError> {
+        self.event(Event::Start(attrs.start(name)))
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), TmxError> {
+        self.event(Event::End(BytesEnd::owned(name.as_bytes().to_vec())))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), TmxError> {
+        self.event(Event::Text(BytesText::from_plain_str(text)))
+    }
+
+    fn map(&mut self, map: &Map) -> Result<(), TmxError> {
+        let staggered = map.orientation == Orientation::Staggered || map.orientation == Orientation::Hexagonal;
+
+        self.start("map", Attrs::new()
+            .set("version", &map.version)
+            .set("orientation", orientation_name(&map.orientation))
+            .set("renderorder", renderorder_name(&map.renderorder))
+            .set("width", map.width)
+            .set("height", map.height)
+            .set("tilewidth", map.tilewidth)
+            .set("tileheight", map.tileheight)
+            .set_if(map.orientation == Orientation::Hexagonal || map.hexsidelength != 0, "hexsidelength", map.hexsidelength)
+            .set_if(staggered || map.staggeraxis != StaggerAxis::X, "staggeraxis", staggeraxis_name(&map.staggeraxis))
+            .set_if(staggered || map.staggerindex != StaggerIndex::Even, "staggerindex", staggerindex_name(&map.staggerindex))
+            .set("infinite", u8::from(map.infinite))
+            .set_opt("backgroundcolor", map.backgroundcolor.as_ref())
+            .set("nextlayerid", next_layer_id(&map.layers))
+            .set("nextobjectid", map.nextobjectid))?;
+
+        self.properties(&map.properties)?;
+        for tileset in &map.tilesets {
+            self.tileset(tileset)?;
+        }
+        self.layers(&map.layers)?;
+
+        self.end("map")
+    }
+
+    fn properties(&mut self, properties: &Properties) -> Result<(), TmxError> {
+        if properties.is_empty() {
+            return Ok(());
+        }
+
+        self.start("properties", Attrs::new())?;
+        for property in properties {
+            self.property(property)?;
+        }
+        self.end("properties")
+    }
+
+    /// Resolved enums are written as strings, the `Project` knows how to read them again.
+    fn property(&mut self, property: &Property) -> Result<(), TmxError> {
+        let attrs = Attrs::new().set("name", &property.name);
+        let typed = |attrs: Attrs, kind: &str, value: String| attrs
+            .set("type", kind)
+            .set_opt("propertytype", property.propertytype.as_ref())
+            .set("value", value);
+
+        match &property.value {
+            // Attribute values can't hold line breaks, Tiled writes those strings as content.
+            PropertyValue::String(value) if value.contains('\n') => {
+                self.start("property", attrs.set_opt("propertytype", property.propertytype.as_ref()))?;
+                self.text(value)?;
+                self.end("property")
+            },
+            PropertyValue::String(value) => self.empty("property", attrs
+                .set_opt("propertytype", property.propertytype.as_ref())
+                .set("value", value)),
+            PropertyValue::Int(value) => self.empty("property", typed(attrs, "int", value.to_string())),
+            PropertyValue::Float(value) => self.empty("property", typed(attrs, "float", value.to_string())),
+            PropertyValue::Bool(value) => self.empty("property", typed(attrs, "bool", value.to_string())),
+            PropertyValue::Color(value) if value.a == 0 && value.r == 0 && value.g == 0 && value.b == 0 =>
+                self.empty("property", typed(attrs, "color", String::new())),
+            PropertyValue::Color(value) => self.empty("property", typed(attrs, "color", value.to_hex())),
+            PropertyValue::File(value) => self.empty("property", typed(attrs, "file", value.to_string_lossy().to_string())),
+            PropertyValue::Object(value) => self.empty("property", typed(attrs, "object", value.to_string())),
+            PropertyValue::Enum(names) => self.empty("property", attrs
+                .set_opt("propertytype", property.propertytype.as_ref())
+                .set("value", names.join(","))),
+            PropertyValue::Class(members) => {
+                let attrs = attrs.set("type", "class").set_opt("propertytype", property.propertytype.as_ref());
+                if members.is_empty() {
+                    return self.empty("property", attrs);
+                }
+                self.start("property", attrs)?;
+                self.properties(members)?;
+                self.end("property")
+            },
+        }
+    }
+
+    fn tileset(&mut self, tileset: &Tileset) -> Result<(), TmxError> {
+        if let Some(source) = &tileset.source {
+            return self.empty("tileset", Attrs::new().set("firstgid", tileset.firstgid).set("source", source));
+        }
+
+        self.start("tileset", Attrs::new()
+            .set("firstgid", tileset.firstgid)
+            .set("name", &tileset.name)
+            .set_opt("class", tileset.class.as_ref())
+            .set("tilewidth", tileset.tilewidth)
+            .set("tileheight", tileset.tileheight)
+            .set_if(tileset.spacing != 0, "spacing", tileset.spacing)
+            .set_if(tileset.margin != 0, "margin", tileset.margin)
+            .set("tilecount", tileset.tilecount)
+            .set("columns", tileset.columns)
+            .set_if(tileset.objectalignment != ObjectAlignment::Unspecified, "objectalignment", objectalignment_name(&tileset.objectalignment))
+            .set_if(tileset.tilerendersize != TileRenderSize::Tile, "tilerendersize", "grid")
+            .set_if(tileset.fillmode != FillMode::Stretch, "fillmode", "preserve-aspect-fit"))?;
+
+        if let Some(offset) = &tileset.tileoffset {
+            self.empty("tileoffset", Attrs::new().set("x", offset.x).set("y", offset.y))?;
+        }
+        if let Some(grid) = &tileset.grid {
+            self.empty("grid", Attrs::new()
+                .set("orientation", orientation_name(&grid.orientation))
+                .set("width", grid.width)
+                .set("height", grid.height))?;
+        }
+        self.properties(&tileset.properties)?;
+        if let Some(image) = &tileset.image {
+            self.image(image)?;
+        }
+        if let Some(transformations) = &tileset.transformations {
+            self.empty("transformations", Attrs::new()
+                .set("hflip", u8::from(transformations.hflip))
+                .set("vflip", u8::from(transformations.vflip))
+                .set("rotate", u8::from(transformations.rotate))
+                .set("preferuntransformed", u8::from(transformations.preferuntransformed)))?;
+        }
+        if !tileset.terraintypes.is_empty() {
+            self.start("terraintypes", Attrs::new())?;
+            for terraintype in &tileset.terraintypes {
+                self.terraintype(terraintype)?;
+            }
+            self.end("terraintypes")?;
+        }
+        for tile in &tileset.tiles {
+            self.tile(tile)?;
+        }
+        if !tileset.wangsets.is_empty() {
+            self.start("wangsets", Attrs::new())?;
+            for wangset in &tileset.wangsets {
+                self.wangset(wangset)?;
+            }
+            self.end("wangsets")?;
+        }
+
+        self.end("tileset")
+    }
+
+    fn tile(&mut self, tile: &Tile) -> Result<(), TmxError> {
+        let attrs = Attrs::new()
+            .set("id", tile.id)
+            .set_opt("class", tile.tile_type.as_ref())
+            .set_opt("terrain", tile.terrain.map(|terrain| terrain_string(&terrain)))
+            .set_if(tile.probability != 1.0, "probability", tile.probability)
+            .set_if(tile.x != 0, "x", tile.x)
+            .set_if(tile.y != 0, "y", tile.y)
+            .set_opt("width", tile.width)
+            .set_opt("height", tile.height);
+
+        if tile.properties.is_empty() && tile.image.is_none() && tile.collision.is_none() && tile.animation.is_empty() {
+            return self.empty("tile", attrs);
+        }
+
+        self.start("tile", attrs)?;
+        self.properties(&tile.properties)?;
+        if let Some(image) = &tile.image {
+            self.image(image)?;
+        }
+        if let Some(collision) = &tile.collision {
+            self.objectgroup(collision)?;
+        }
+        if !tile.animation.is_empty() {
+            self.start("animation", Attrs::new())?;
+            for frame in &tile.animation {
+                self.empty("frame", Attrs::new().set("tileid", frame.tileid).set("duration", frame.duration))?;
+            }
+            self.end("animation")?;
+        }
+        self.end("tile")
+    }
+
+    fn terraintype(&mut self, terraintype: &TerrainType) -> Result<(), TmxError> {
+        let attrs = Attrs::new().set("name", &terraintype.name).set("tile", terraintype.tile);
+        if terraintype.properties.is_empty() {
+            return self.empty("terrain", attrs);
+        }
+
+        self.start("terrain", attrs)?;
+        self.properties(&terraintype.properties)?;
+        self.end("terrain")
+    }
+
+    fn wangset(&mut self, wangset: &WangSet) -> Result<(), TmxError> {
+        self.start("wangset", Attrs::new()
+            .set("name", &wangset.name)
+            .set_opt("class", wangset.class.as_ref())
+            .set("type", wangsettype_name(&wangset.wangsettype))
+            .set("tile", wangset.tile.unwrap_or(-1)))?;
+
+        self.properties(&wangset.properties)?;
+        for color in &wangset.colors {
+            self.wangcolor(color)?;
+        }
+        for tile in &wangset.tiles {
+            let wangid: Vec<String> = tile.wangid.0.iter().map(|color| color.to_string()).collect();
+            self.empty("wangtile", Attrs::new().set("tileid", tile.tileid).set("wangid", wangid.join(",")))?;
+        }
+
+        self.end("wangset")
+    }
+
+    fn wangcolor(&mut self, color: &WangColor) -> Result<(), TmxError> {
+        let attrs = Attrs::new()
+            .set("name", &color.name)
+            .set_opt("class", color.class.as_ref())
+            .set("color", color.color.to_hex())
+            .set("tile", color.tile.unwrap_or(-1))
+            .set("probability", color.probability);
+
+        if color.properties.is_empty() {
+            return self.empty("wangcolor", attrs);
+        }
+
+        self.start("wangcolor", attrs)?;
+        self.properties(&color.properties)?;
+        self.end("wangcolor")
+    }
+
+    fn image(&mut self, image: &Image) -> Result<(), TmxError> {
+        self.empty("image", Attrs::new()
+            .set_opt("format", image.format.as_ref())
+            .set("source", &image.source)
+            .set_opt("trans", image.trans.as_ref())
+            .set("width", image.width)
+            .set_opt("height", image.height))
+    }
+
+    fn layers(&mut self, layers: &[LayerKind]) -> Result<(), TmxError> {
+        for layer in layers {
+            match layer {
+                LayerKind::Tile(layer) => self.layer(layer)?,
+                LayerKind::Object(objectgroup) => self.objectgroup(objectgroup)?,
+                LayerKind::Image(imagelayer) => self.imagelayer(imagelayer)?,
+                LayerKind::Group(group) => self.group(group)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn layer(&mut self, layer: &Layer) -> Result<(), TmxError> {
+        self.start("layer", Attrs::new()
+            .set("id", layer.id)
+            .set("name", &layer.name)
+            .set_if(layer.x != 0.0, "x", layer.x)
+            .set_if(layer.y != 0.0, "y", layer.y)
+            .set("width", layer.width)
+            .set("height", layer.height)
+            .set_if(layer.opacity != 1.0, "opacity", layer.opacity)
+            .set_if(!layer.visible, "visible", 0)
+            .set_opt("tintcolor", layer.tintcolor.map(|c| c.to_hex()))
+            .set_if(layer.offsetx != 0, "offsetx", layer.offsetx)
+            .set_if(layer.offsety != 0, "offsety", layer.offsety))?;
+
+        self.properties(&layer.properties)?;
+
+        let attrs = Attrs::new()
+            .set_opt("encoding", encoding_name(&layer.encoding))
+            .set_opt("compression", layer.compression.filter(|_| layer.encoding == DataEncoding::Base64).map(|c| compression_name(&c)));
+
+        if layer.infinite {
+            self.start("data", attrs)?;
+            for chunk in &layer.chunks {
+                self.start("chunk", Attrs::new()
+                    .set("x", chunk.x)
+                    .set("y", chunk.y)
+                    .set("width", chunk.width)
+                    .set("height", chunk.height))?;
+                self.tile_data(&chunk.data, chunk.width, layer)?;
+                self.end("chunk")?;
+            }
+            self.end("data")?;
+        } else {
+            self.start("data", attrs)?;
+            self.tile_data(&layer.data, layer.width, layer)?;
+            self.end("data")?;
+        }
+
+        self.end("layer")
+    }
+
+    fn tile_data(&mut self, data: &[Gid], width: i32, layer: &Layer) -> Result<(), TmxError> {
+        match layer.encoding {
+            DataEncoding::Csv => self.text(&encoding::encode_csv(data, width)),
+            DataEncoding::Base64 => self.text(&encoding::encode_base64(data, layer.compression)?),
+            DataEncoding::Xml => {
+                for gid in data {
+                    self.empty("tile", Attrs::new().set_if(gid.0 != 0, "gid", gid))?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn objectgroup(&mut self, objectgroup: &Objectgroup) -> Result<(), TmxError> {
+        let attrs = Attrs::new()
+            .set_if(objectgroup.draworder == DrawOrder::Index, "draworder", "index")
+            .set("id", objectgroup.id)
+            .set_if(!objectgroup.name.is_empty(), "name", &objectgroup.name)
+            .set_if(!objectgroup.color.is_empty(), "color", &objectgroup.color)
+            .set_if(objectgroup.x != 0.0, "x", objectgroup.x)
+            .set_if(objectgroup.y != 0.0, "y", objectgroup.y)
+            .set_if(objectgroup.opacity != 1.0, "opacity", objectgroup.opacity)
+            .set_if(!objectgroup.visible, "visible", 0)
+            .set_opt("tintcolor", objectgroup.tintcolor.map(|c| c.to_hex()))
+            .set_if(objectgroup.offsetx != 0, "offsetx", objectgroup.offsetx)
+            .set_if(objectgroup.offsety != 0, "offsety", objectgroup.offsety);
+
+        if objectgroup.properties.is_empty() && objectgroup.objects.is_empty() {
+            return self.empty("objectgroup", attrs);
+        }
+
+        self.start("objectgroup", attrs)?;
+        self.properties(&objectgroup.properties)?;
+        for object in &objectgroup.objects {
+            self.object(object)?;
+        }
+        self.end("objectgroup")
+    }
+
+    /// Objects based on a template are written with all of their values, not just the ones differing from the template.
+    /// Even default values are written for them, they might override a different value of the template.
+    fn object(&mut self, object: &Object) -> Result<(), TmxError> {
+        let templated = object.template.is_some();
+        let attrs = Attrs::new()
+            .set("id", object.id)
+            .set_opt("template", object.template.as_ref())
+            .set_opt("name", object.name.as_ref())
+            .set_opt("type", object.object_type.as_ref())
+            .set_opt("gid", object.gid)
+            .set("x", object.x)
+            .set("y", object.y)
+            .set_if(templated || object.width != 0.0, "width", object.width)
+            .set_if(templated || object.height != 0.0, "height", object.height)
+            .set_if(templated || object.rotation != 0.0, "rotation", object.rotation)
+            .set_if(templated || !object.visible, "visible", u8::from(object.visible));
+
+        if object.properties.is_empty() && object.shape == ObjectShape::Rectangle {
+            return self.empty("object", attrs);
+        }
+
+        self.start("object", attrs)?;
+        self.properties(&object.properties)?;
+        match &object.shape {
+            ObjectShape::Rectangle => (),
+            ObjectShape::Ellipse => self.empty("ellipse", Attrs::new())?,
+            ObjectShape::Point => self.empty("point", Attrs::new())?,
+            ObjectShape::Polygon(points) => self.empty("polygon", Attrs::new().set("points", points_string(points)))?,
+            ObjectShape::Polyline(points) => self.empty("polyline", Attrs::new().set("points", points_string(points)))?,
+            ObjectShape::Text(text) => self.text_shape(text)?,
+        }
+        self.end("object")
+    }
+
+    fn text_shape(&mut self, text: &Text) -> Result<(), TmxError> {
+        self.start("text", Attrs::new()
+            .set_if(text.fontfamily != "sans-serif", "fontfamily", &text.fontfamily)
+            .set_if(text.pixelsize != 16, "pixelsize", text.pixelsize)
+            .set_if(text.wrap, "wrap", 1)
+            .set_if(text.color != "#000000", "color", &text.color)
+            .set_if(text.bold, "bold", 1)
+            .set_if(text.italic, "italic", 1)
+            .set_if(text.underline, "underline", 1)
+            .set_if(text.strikeout, "strikeout", 1)
+            .set_if(!text.kerning, "kerning", 0)
+            .set_if(text.halign != HorizontalAlignment::Left, "halign", halign_name(&text.halign))
+            .set_if(text.valign != VerticalAlignment::Top, "valign", valign_name(&text.valign)))?;
+        self.text(&text.text)?;
+        self.end("text")
+    }
+
+    fn imagelayer(&mut self, imagelayer: &Imagelayer) -> Result<(), TmxError> {
+        let attrs = Attrs::new()
+            .set("id", imagelayer.id)
+            .set("name", &imagelayer.name)
+            .set_if(imagelayer.offsetx != 0, "offsetx", imagelayer.offsetx)
+            .set_if(imagelayer.offsety != 0, "offsety", imagelayer.offsety)
+            .set_if(imagelayer.x != 0.0, "x", imagelayer.x)
+            .set_if(imagelayer.y != 0.0, "y", imagelayer.y)
+            .set_if(imagelayer.opacity != 1.0, "opacity", imagelayer.opacity)
+            .set_if(!imagelayer.visible, "visible", 0)
+            .set_opt("tintcolor", imagelayer.tintcolor.map(|c| c.to_hex()))
+            .set_if(imagelayer.repeatx, "repeatx", 1)
+            .set_if(imagelayer.repeaty, "repeaty", 1)
+            .set_if(imagelayer.parallaxx != 1.0, "parallaxx", imagelayer.parallaxx)
+            .set_if(imagelayer.parallaxy != 1.0, "parallaxy", imagelayer.parallaxy);
+
+        if imagelayer.properties.is_empty() && imagelayer.image.is_none() {
+            return self.empty("imagelayer", attrs);
+        }
+
+        self.start("imagelayer", attrs)?;
+        self.properties(&imagelayer.properties)?;
+        if let Some(image) = &imagelayer.image {
+            self.image(image)?;
+        }
+        self.end("imagelayer")
+    }
+
+    fn group(&mut self, group: &Group) -> Result<(), TmxError> {
+        let attrs = Attrs::new()
+            .set("id", group.id)
+            .set("name", &group.name)
+            .set_if(group.offsetx != 0, "offsetx", group.offsetx)
+            .set_if(group.offsety != 0, "offsety", group.offsety)
+            .set_if(group.opacity != 1.0, "opacity", group.opacity)
+            .set_if(!group.visible, "visible", 0)
+            .set_opt("tintcolor", group.tintcolor.map(|c| c.to_hex()));
+
+        if group.properties.is_empty() && group.layers.is_empty() {
+            return self.empty("group", attrs);
+        }
+
+        self.start("group", attrs)?;
+        self.properties(&group.properties)?;
+        self.layers(&group.layers)?;
+        self.end("group")
+    }
+}
+
+/// Tiled needs it to hand out ids to new layers.
+fn next_layer_id(layers: &[LayerKind]) -> i64 {
+    layers.iter()
+        .map(|layer| match layer {
+            LayerKind::Group(group) => next_layer_id(&group.layers).max(group.id + 1),
+            layer => layer.id() + 1,
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+fn points_string(points: &[(f32, f32)]) -> String {
+    points.iter().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<String>>().join(" ")
+}
+
+fn terrain_string(terrain: &[Option<usize>; 4]) -> String {
+    let corners: Vec<String> = terrain.iter()
+        .map(|corner| corner.map(|index| index.to_string()).unwrap_or_default())
+        .collect();
+    corners.join(",")
+}
+
+fn wangsettype_name(wangsettype: &WangSetType) -> &'static str {
+    match wangsettype {
+        WangSetType::Corner => "corner",
+        WangSetType::Edge => "edge",
+        WangSetType::Mixed => "mixed",
+    }
+}
+
+fn orientation_name(orientation: &Orientation) -> &'static str {
+    match orientation {
+        Orientation::Orthogonal => "orthogonal",
+        Orientation::Isometric => "isometric",
+        Orientation::Staggered => "staggered",
+        Orientation::Hexagonal => "hexagonal",
+    }
+}
+
+fn renderorder_name(renderorder: &Renderorder) -> &'static str {
+    match renderorder {
+        Renderorder::RightDown => "right-down",
+        Renderorder::RightUp => "right-up",
+        Renderorder::LeftDown => "left-down",
+        Renderorder::LeftUp => "left-up",
+    }
+}
+
+fn staggeraxis_name(staggeraxis: &StaggerAxis) -> &'static str {
+    match staggeraxis {
+        StaggerAxis::X => "x",
+        StaggerAxis::Y => "y",
+    }
+}
+
+fn staggerindex_name(staggerindex: &StaggerIndex) -> &'static str {
+    match staggerindex {
+        StaggerIndex::Even => "even",
+        StaggerIndex::Odd => "odd",
+    }
+}
+
+fn objectalignment_name(objectalignment: &ObjectAlignment) -> &'static str {
+    match objectalignment {
+        ObjectAlignment::Unspecified => "unspecified",
+        ObjectAlignment::TopLeft => "topleft",
+        ObjectAlignment::Top => "top",
+        ObjectAlignment::TopRight => "topright",
+        ObjectAlignment::Left => "left",
+        ObjectAlignment::Center => "center",
+        ObjectAlignment::Right => "right",
+        ObjectAlignment::BottomLeft => "bottomleft",
+        ObjectAlignment::Bottom => "bottom",
+        ObjectAlignment::BottomRight => "bottomright",
+    }
+}
+
+fn encoding_name(encoding: &DataEncoding) -> Option<&'static str> {
+    match encoding {
+        DataEncoding::Xml => None,
+        DataEncoding::Csv => Some("csv"),
+        DataEncoding::Base64 => Some("base64"),
+    }
+}
+
+fn compression_name(compression: &Compression) -> &'static str {
+    match compression {
+        Compression::Zlib => "zlib",
+        Compression::Gzip => "gzip",
+        Compression::Zstd => "zstd",
+    }
+}
+
+fn halign_name(halign: &HorizontalAlignment) -> &'static str {
+    match halign {
+        HorizontalAlignment::Left => "left",
+        HorizontalAlignment::Center => "center",
+        HorizontalAlignment::Right => "right",
+        HorizontalAlignment::Justify => "justify",
+    }
+}
+
+fn valign_name(valign: &VerticalAlignment) -> &'static str {
+    match valign {
+        VerticalAlignment::Top => "top",
+        VerticalAlignment::Center => "center",
+        VerticalAlignment::Bottom => "bottom",
+    }
+}